@@ -0,0 +1,199 @@
+use crate::{Error, Result, TempoMap};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Parameter values for a whole run and parameter changes at positions of the song.
+///
+/// As JSON:
+///
+/// ```json
+/// {
+///     "set": { "Channel": 3, "MPE": true },
+///     "automation": [
+///         { "bar": 5, "param": "Active", "value": false },
+///         { "bar": 9, "beat": 3, "param": "Program Change", "value": 12 },
+///         { "seconds": 42.5, "param": "Active", "value": "On" }
+///     ]
+/// }
+/// ```
+///
+/// Or as a script, one setting per line:
+///
+/// ```text
+/// # comments start with a hash
+/// set Channel = 3
+/// bar 5: Active = Off
+/// bar 9 beat 3: Program Change = 12
+/// tick 1920: Mod Wheel = 64
+/// seconds 42.5: Active = On
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Automation {
+    #[serde(default)]
+    pub set: BTreeMap<String, Value>,
+
+    #[serde(default)]
+    pub automation: Vec<Point>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Point {
+    #[serde(flatten)]
+    pub position: Position,
+    pub param: String,
+    pub value: Value,
+}
+
+/// A position in the song, bars and beats are counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Position {
+    Bar {
+        bar: u64,
+        #[serde(default = "first_beat")]
+        beat: f64,
+    },
+    Tick {
+        tick: u64,
+    },
+    Seconds {
+        seconds: f64,
+    },
+}
+
+fn first_beat() -> f64 {
+    1.0
+}
+
+impl Position {
+    pub fn tick(self, map: &TempoMap) -> Result<u64> {
+        match self {
+            Self::Bar { bar, beat } => map.bar(bar, beat).ok_or_else(|| {
+                Error::Position(format!(
+                    "bar {bar} does not exist, bars start at 1 and need a file timed in beats"
+                ))
+            }),
+            Self::Tick { tick } => Ok(tick),
+            Self::Seconds { seconds } => Ok(map.tick(seconds)),
+        }
+    }
+}
+
+impl Automation {
+    /// Reads a JSON file, or a script for any other extension.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => {
+                Ok(serde_json::from_str(&text)?)
+            }
+            _ => Self::parse_script(&text),
+        }
+    }
+
+    pub fn parse_script(text: &str) -> Result<Self> {
+        let mut automation = Self::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let invalid = || Error::Script(index + 1, line.to_owned());
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(setting) = line.strip_prefix("set ") {
+                let (param, value) = assignment(setting).ok_or_else(invalid)?;
+                automation.set.insert(param, value);
+                continue;
+            }
+
+            let (position, setting) = line.split_once(':').ok_or_else(invalid)?;
+            let position = script_position(position).ok_or_else(invalid)?;
+            let (param, value) = assignment(setting).ok_or_else(invalid)?;
+
+            automation.automation.push(Point {
+                position,
+                param,
+                value,
+            });
+        }
+
+        Ok(automation)
+    }
+}
+
+/// `name = value`, the value kept as text for the parameter to parse.
+fn assignment(text: &str) -> Option<(String, Value)> {
+    let (param, value) = text.split_once('=')?;
+    let (param, value) = (param.trim(), value.trim());
+
+    (!param.is_empty() && !value.is_empty())
+        .then(|| (param.to_owned(), Value::String(value.to_owned())))
+}
+
+fn script_position(text: &str) -> Option<Position> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+
+    match words[..] {
+        ["bar", bar] => Some(Position::Bar {
+            bar: bar.parse().ok()?,
+            beat: first_beat(),
+        }),
+        ["bar", bar, "beat", beat] => Some(Position::Bar {
+            bar: bar.parse().ok()?,
+            beat: beat.parse().ok()?,
+        }),
+        ["tick", tick] => Some(Position::Tick {
+            tick: tick.parse().ok()?,
+        }),
+        ["seconds", seconds] => Some(Position::Seconds {
+            seconds: seconds.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+/// The text a parameter parses for a value: booleans as the "On" and "Off" of toggles.
+pub fn value_text(value: &Value) -> String {
+    match value {
+        Value::Bool(true) => "On".to_owned(),
+        Value::Bool(false) => "Off".to_owned(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_and_json_agree() {
+        let script = Automation::parse_script(
+            "set Channel = 3\n\n# toggle active\nbar 5: Active = Off\nbar 9 beat 3: Program Change = 12\n",
+        )
+        .unwrap();
+
+        let json: Automation = serde_json::from_str(
+            r#"{"set": {"Channel": "3"}, "automation": [
+                {"bar": 5, "param": "Active", "value": "Off"},
+                {"bar": 9, "beat": 3, "param": "Program Change", "value": "12"}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(script, json);
+    }
+
+    #[test]
+    fn invalid_lines_are_reported() {
+        assert!(matches!(
+            Automation::parse_script("bar 5: Active = On\nbar five: Active = Off"),
+            Err(Error::Script(2, _))
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{ClapLibrary, MidiEvent, ParamChange, PluginInstance, Result};
+
+/// A player's input for the property tests of the plugin crates: events and parameter changes
+/// one after the other, the keys and pedals still down are released at the end.
+pub struct Performance<'a> {
+    changes: Vec<ParamChange>,
+    events: Vec<MidiEvent>,
+    /// The keys down, per channel.
+    keys: [u128; 16],
+    plugin: PluginInstance<'a>,
+    sample: u64,
+    sample_rate: f64,
+}
+
+impl<'a> Performance<'a> {
+    pub fn new(library: &'a ClapLibrary) -> Result<Self> {
+        let sample_rate = 48_000.0;
+
+        Ok(Self {
+            changes: Vec::new(),
+            events: Vec::new(),
+            keys: [0; 16],
+            plugin: PluginInstance::new(library, sample_rate, 256)?,
+            sample: 0,
+            sample_rate,
+        })
+    }
+
+    pub fn wait(&mut self, samples: u64) {
+        self.sample += samples;
+    }
+
+    pub fn note_on(&mut self, channel: u8, note: u8, velocity: u8) {
+        self.keys[channel as usize & 0x0F] |= 1 << (note & 0x7F);
+        self.send([0x90 | channel, note, velocity.max(1)]);
+    }
+
+    pub fn note_off(&mut self, channel: u8, note: u8) {
+        self.keys[channel as usize & 0x0F] &= !(1 << (note & 0x7F));
+        self.send([0x80 | channel, note, 64]);
+    }
+
+    pub fn controller(&mut self, channel: u8, cc: u8, value: u8) {
+        self.send([0xB0 | channel, cc, value]);
+    }
+
+    pub fn param(&mut self, name: &str, text: &str) -> Result<()> {
+        let (id, value) = self.plugin.param_value(name, text)?;
+
+        self.changes.push(ParamChange {
+            sample: self.sample,
+            id,
+            value,
+        });
+
+        Ok(())
+    }
+
+    /// Releases the keys and the damper pedals then runs the performance, with a second for the
+    /// plugin to settle.
+    pub fn finish(mut self) -> Result<Vec<MidiEvent>> {
+        self.wait(1);
+
+        for channel in 0..16 {
+            for note in 0..128 {
+                if self.keys[channel as usize] & (1 << note) != 0 {
+                    self.note_off(channel, note);
+                }
+            }
+
+            self.controller(channel, 64, 0);
+        }
+
+        let length = self.sample + self.sample_rate as u64;
+        self.plugin.process(&self.events, &self.changes, length)
+    }
+
+    fn send(&mut self, data: [u8; 3]) {
+        self.events.push(MidiEvent {
+            sample: self.sample,
+            data,
+        });
+    }
+}
+
+/// Checks that the notes sent are released: every note on is matched by a note off on the same
+/// channel and no note off is sent for a note that is not sounding. A note on of a sounding note
+/// retriggers it and needs a single note off, as on a synth.
+pub fn check_notes(events: &[MidiEvent]) -> std::result::Result<(), String> {
+    let mut sounding = [0u128; 16];
+
+    for event in events {
+        let [status, note, velocity] = event.data;
+        let channel = (status & 0x0F) as usize;
+        let mask = 1u128 << (note & 0x7F);
+
+        match status & 0xF0 {
+            0x90 if velocity > 0 => sounding[channel] |= mask,
+            0x80 | 0x90 => {
+                if sounding[channel] & mask == 0 {
+                    return Err(format!(
+                        "note off of note {note} on channel {} at sample {} was never opened",
+                        channel + 1,
+                        event.sample
+                    ));
+                }
+
+                sounding[channel] &= !mask;
+            }
+            _ => (),
+        }
+    }
+
+    let hanging: Vec<String> = sounding
+        .iter()
+        .enumerate()
+        .flat_map(|(channel, notes)| {
+            (0..128)
+                .filter(move |note| notes & (1u128 << note) != 0)
+                .map(move |note| format!("{note} on channel {}", channel + 1))
+        })
+        .collect();
+
+    if hanging.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("notes left hanging: {}", hanging.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(data: &[[u8; 3]]) -> Vec<MidiEvent> {
+        data.iter()
+            .enumerate()
+            .map(|(sample, data)| MidiEvent {
+                sample: sample as u64,
+                data: *data,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn retriggered_notes_need_one_note_off() {
+        let played = events(&[[0x90, 60, 100], [0x90, 60, 90], [0x80, 60, 0]]);
+        assert_eq!(check_notes(&played), Ok(()));
+    }
+
+    #[test]
+    fn note_offs_match_their_channel() {
+        let hanging = events(&[[0x90, 60, 100], [0x81, 60, 0]]);
+        assert!(check_notes(&hanging).is_err());
+
+        let stray = events(&[[0x90, 60, 100], [0x80, 60, 0], [0x90, 60, 0]]);
+        assert!(check_notes(&stray).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Load(libloading::Error),
+    Midi(midly::Error),
+
+    /// The plugin refused a call of the CLAP API.
+    Plugin(&'static str),
+
+    /// An unknown parameter or a value the parameter does not accept.
+    Param(String),
+
+    /// A position of the automation outside of the song.
+    Position(String),
+
+    /// A line of an automation script that does not parse, counted from 1.
+    Script(usize, String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Json(error) => write!(f, "invalid automation: {error}"),
+            Self::Load(error) => write!(f, "cannot load the plugin: {error}"),
+            Self::Midi(error) => write!(f, "invalid MIDI file: {error}"),
+            Self::Plugin(call) => write!(f, "the plugin failed to {call}"),
+            Self::Param(message) | Self::Position(message) => f.write_str(message),
+            Self::Script(line, text) => write!(f, "invalid automation on line {line}: {text}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+impl From<libloading::Error> for Error {
+    fn from(error: libloading::Error) -> Self {
+        Self::Load(error)
+    }
+}
+
+impl From<midly::Error> for Error {
+    fn from(error: midly::Error) -> Self {
+        Self::Midi(error)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{Automation, ClapLibrary, Render, Song, built_plugin};
+use std::path::Path;
+
+/// The variable that rewrites the expected logs with the current output instead of comparing.
+const BLESS: &str = "MIDI_CLI_BLESS";
+
+/// Runs the golden case `case` of `dir` through the plugin built by the crate `plugin` and panics
+/// when the result differs from the expected one, for the tests of the plugin crates.
+///
+/// The case is made of these files, named after it:
+///
+/// - `<case>.mid`, the input;
+/// - `<case>.auto` or `<case>.json`, optional automation;
+/// - `<case>.log`, the expected event log, see [`Song::event_log`], or `<case>.out.mid`, the
+///   expected file, compared through its event log.
+///
+/// With `MIDI_CLI_BLESS` set, `<case>.log` is written with the result instead.
+pub fn assert_golden(plugin: &str, dir: &Path, case: &str) {
+    let path = built_plugin(plugin)
+        .unwrap_or_else(|| panic!("the plugin library of {plugin} is not built"));
+    let library = ClapLibrary::load(&path).unwrap_or_else(|error| panic!("{error}"));
+
+    let input = Song::load(&dir.join(format!("{case}.mid")))
+        .unwrap_or_else(|error| panic!("{case}.mid: {error}"));
+
+    let automation = ["auto", "json"]
+        .iter()
+        .map(|extension| dir.join(format!("{case}.{extension}")))
+        .find(|path| path.exists())
+        .map(|path| Automation::load(&path).unwrap_or_else(|error| panic!("{case}: {error}")))
+        .unwrap_or_default();
+
+    let actual = Render::default()
+        .run(&library, &input, &automation)
+        .unwrap_or_else(|error| panic!("{case}: {error}"))
+        .event_log();
+
+    let log = dir.join(format!("{case}.log"));
+
+    if std::env::var_os(BLESS).is_some() {
+        std::fs::write(&log, &actual).unwrap_or_else(|error| panic!("{case}.log: {error}"));
+        return;
+    }
+
+    let expected = match std::fs::read_to_string(&log) {
+        Ok(expected) => expected,
+        Err(_) => Song::load(&dir.join(format!("{case}.out.mid")))
+            .unwrap_or_else(|error| panic!("{case} has no expected output: {error}"))
+            .event_log(),
+    };
+
+    assert!(
+        expected == actual,
+        "{case}: the output differs from the expected one, run with {BLESS}=1 to accept it\n\
+         --- expected\n{expected}--- actual\n{actual}"
+    );
+}
+
+/// [`assert_golden`] for the case `name` of the `tests/golden` directory of a plugin crate,
+/// `crate_dir` being its `CARGO_MANIFEST_DIR`. The plugin is the crate, named after its directory.
+pub fn golden_case(crate_dir: &str, name: &str) {
+    let crate_dir = Path::new(crate_dir);
+    let plugin = crate_dir
+        .file_name()
+        .and_then(|plugin| plugin.to_str())
+        .unwrap_or_else(|| panic!("{} is not a crate directory", crate_dir.display()));
+
+    assert_golden(plugin, &crate_dir.join("tests").join("golden"), name);
+}
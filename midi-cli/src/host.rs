@@ -0,0 +1,526 @@
+use crate::{Error, Result};
+use clap_sys::{
+    entry::clap_plugin_entry,
+    events::{
+        CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI, CLAP_EVENT_NOTE_CHOKE,
+        CLAP_EVENT_NOTE_EXPRESSION, CLAP_EVENT_NOTE_OFF, CLAP_EVENT_NOTE_ON,
+        CLAP_EVENT_PARAM_VALUE, CLAP_NOTE_EXPRESSION_PRESSURE, clap_event_header, clap_event_midi,
+        clap_event_note, clap_event_note_expression, clap_event_param_value, clap_input_events,
+        clap_output_events,
+    },
+    ext::params::{CLAP_EXT_PARAMS, clap_param_info, clap_plugin_params},
+    factory::plugin_factory::{CLAP_PLUGIN_FACTORY_ID, clap_plugin_factory},
+    host::clap_host,
+    id::clap_id,
+    plugin::clap_plugin,
+    process::{CLAP_PROCESS_ERROR, clap_process},
+    version::CLAP_VERSION,
+};
+use libloading::Library;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::path::{Path, PathBuf};
+use std::{mem, ptr};
+
+/// A MIDI 1.0 channel message at an absolute sample position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MidiEvent {
+    pub sample: u64,
+    pub data: [u8; 3],
+}
+
+impl MidiEvent {
+    pub fn new(sample: u64, bytes: &[u8]) -> Self {
+        let mut data = [0; 3];
+        let len = bytes.len().min(3);
+
+        data[..len].copy_from_slice(&bytes[..len]);
+        Self { sample, data }
+    }
+
+    /// The bytes of the message, program change and channel pressure only have two.
+    pub fn bytes(&self) -> &[u8] {
+        match self.data[0] & 0xF0 {
+            0xC0 | 0xD0 => &self.data[..2],
+            _ => &self.data,
+        }
+    }
+}
+
+/// A parameter set at an absolute sample position, `value` is in the plugin's CLAP units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamChange {
+    pub sample: u64,
+    pub id: clap_id,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamInfo {
+    pub id: clap_id,
+    pub name: String,
+}
+
+/// The binary of a CLAP plugin, a `.clap` bundle or the `cdylib` of one of the plugin crates.
+pub struct ClapLibrary {
+    entry: *const clap_plugin_entry,
+    _library: Library,
+}
+
+impl ClapLibrary {
+    pub fn load(path: &Path) -> Result<Self> {
+        let binary = bundle_binary(path);
+
+        unsafe {
+            let library = Library::new(&binary)?;
+            let entry = *library.get::<*const clap_plugin_entry>(b"clap_entry\0")?;
+
+            let path = CString::new(binary.to_string_lossy().into_owned())
+                .map_err(|_| Error::Plugin("load a path with a nul byte"))?;
+
+            match (*entry).init {
+                Some(init) if init(path.as_ptr()) => Ok(Self {
+                    entry,
+                    _library: library,
+                }),
+                _ => Err(Error::Plugin("initialize its entry point")),
+            }
+        }
+    }
+}
+
+impl Drop for ClapLibrary {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(deinit) = (*self.entry).deinit {
+                deinit();
+            }
+        }
+    }
+}
+
+/// The executable inside a macOS bundle, the file itself everywhere else.
+fn bundle_binary(path: &Path) -> PathBuf {
+    match path.file_stem() {
+        Some(name) if path.is_dir() => path.join("Contents").join("MacOS").join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The plugin binary built for the crate `name` next to the running test or executable. The
+/// `cdylib` of a plugin crate exports the same entry point as its bundle.
+pub fn built_plugin(name: &str) -> Option<PathBuf> {
+    use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+
+    let file = format!("{DLL_PREFIX}{}{DLL_SUFFIX}", name.replace('-', "_"));
+    let exe = std::env::current_exe().ok()?;
+
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .map(|dir| dir.join(&file))
+        .find(|path| path.exists())
+}
+
+/// The first plugin of a library, activated and processing. Processing is offline: every call
+/// runs as fast as possible on the calling thread.
+pub struct PluginInstance<'a> {
+    block_size: u32,
+    // the plugin keeps a pointer to the host for its whole life
+    _host: Box<clap_host>,
+    _library: &'a ClapLibrary,
+    params: *const clap_plugin_params,
+    plugin: *const clap_plugin,
+    steps: i64,
+}
+
+impl<'a> PluginInstance<'a> {
+    pub fn new(library: &'a ClapLibrary, sample_rate: f64, block_size: u32) -> Result<Self> {
+        let host = Box::new(clap_host {
+            clap_version: CLAP_VERSION,
+            host_data: ptr::null_mut(),
+            name: c"midi-cli".as_ptr(),
+            vendor: c"".as_ptr(),
+            url: c"".as_ptr(),
+            version: c"0.1.0".as_ptr(),
+            get_extension: Some(host_get_extension),
+            request_restart: Some(host_request),
+            request_process: Some(host_request),
+            request_callback: Some(host_request),
+        });
+
+        unsafe {
+            let get_factory = (*library.entry)
+                .get_factory
+                .ok_or(Error::Plugin("provide a plugin factory"))?;
+            let factory =
+                get_factory(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory;
+
+            if factory.is_null() {
+                return Err(Error::Plugin("provide a plugin factory"));
+            }
+
+            let descriptor = match (*factory).get_plugin_descriptor {
+                Some(get_plugin_descriptor) => get_plugin_descriptor(factory, 0),
+                None => ptr::null(),
+            };
+
+            if descriptor.is_null() {
+                return Err(Error::Plugin("describe its plugin"));
+            }
+
+            let plugin = match (*factory).create_plugin {
+                Some(create_plugin) => create_plugin(factory, &*host, (*descriptor).id),
+                None => ptr::null(),
+            };
+
+            if plugin.is_null() {
+                return Err(Error::Plugin("create its plugin"));
+            }
+
+            let params = match (*plugin).get_extension {
+                Some(get_extension) => get_extension(plugin, CLAP_EXT_PARAMS.as_ptr()),
+                None => ptr::null(),
+            } as *const clap_plugin_params;
+
+            let mut instance = Self {
+                block_size,
+                _host: host,
+                _library: library,
+                params,
+                plugin,
+                steps: 0,
+            };
+
+            instance.start(sample_rate)?;
+            Ok(instance)
+        }
+    }
+
+    fn start(&mut self, sample_rate: f64) -> Result<()> {
+        unsafe {
+            let plugin = &*self.plugin;
+
+            if !plugin.init.is_some_and(|init| init(self.plugin)) {
+                return Err(Error::Plugin("initialize"));
+            }
+
+            if !plugin
+                .activate
+                .is_some_and(|activate| activate(self.plugin, sample_rate, 1, self.block_size))
+            {
+                return Err(Error::Plugin("activate"));
+            }
+
+            if !plugin
+                .start_processing
+                .is_some_and(|start_processing| start_processing(self.plugin))
+            {
+                return Err(Error::Plugin("start processing"));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn params(&self) -> Vec<ParamInfo> {
+        let Some(params) = (unsafe { self.params.as_ref() }) else {
+            return Vec::new();
+        };
+
+        let (Some(count), Some(get_info)) = (params.count, params.get_info) else {
+            return Vec::new();
+        };
+
+        unsafe {
+            (0..count(self.plugin))
+                .filter_map(|index| {
+                    let mut info: clap_param_info = mem::zeroed();
+
+                    get_info(self.plugin, index, &mut info).then(|| ParamInfo {
+                        id: info.id,
+                        name: CStr::from_ptr(info.name.as_ptr())
+                            .to_string_lossy()
+                            .into_owned(),
+                    })
+                })
+                .collect()
+        }
+    }
+
+    /// The id of the parameter named `name` and the value of `text`, parsed by the parameter the
+    /// way a host text field would.
+    pub fn param_value(&self, name: &str, text: &str) -> Result<(clap_id, f64)> {
+        let param = self
+            .params()
+            .into_iter()
+            .find(|param| param.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::Param(format!("unknown parameter \"{name}\"")))?;
+
+        let invalid = || Error::Param(format!("invalid value \"{text}\" for \"{}\"", param.name));
+        let text = CString::new(text).map_err(|_| invalid())?;
+        let mut value = 0.0;
+
+        let parsed = unsafe {
+            (*self.params).text_to_value.is_some_and(|text_to_value| {
+                text_to_value(self.plugin, param.id, text.as_ptr(), &mut value)
+            })
+        };
+
+        if parsed {
+            Ok((param.id, value))
+        } else {
+            Err(invalid())
+        }
+    }
+
+    /// Runs `length` samples through the plugin, the events and parameter changes sorted by
+    /// sample. Blocks are split at the parameter changes so they apply on their exact sample.
+    /// Returns the MIDI 1.0 messages sent by the plugin, note expressions other than pressure
+    /// have no MIDI 1.0 equivalent and are left out.
+    pub fn process(
+        &mut self,
+        events: &[MidiEvent],
+        changes: &[ParamChange],
+        length: u64,
+    ) -> Result<Vec<MidiEvent>> {
+        let mut events = events.iter().peekable();
+        let mut changes = changes.iter().peekable();
+        let mut output = OutputList::default();
+        let mut position = 0;
+
+        while position < length {
+            let mut params = Vec::new();
+
+            while let Some(change) = changes.next_if(|change| change.sample <= position) {
+                params.push(param_event(change.id, change.value));
+            }
+
+            let mut end = (position + self.block_size as u64).min(length);
+
+            if let Some(change) = changes.peek() {
+                end = end.min(change.sample);
+            }
+
+            let mut midi = Vec::new();
+
+            while let Some(event) = events.next_if(|event| event.sample < end) {
+                let time = event.sample.saturating_sub(position) as u32;
+                midi.push(midi_event(time, event.data));
+            }
+
+            output.offset = position;
+            self.process_block((end - position) as u32, &params, &midi, &mut output)?;
+            position = end;
+        }
+
+        Ok(output.events)
+    }
+
+    fn process_block(
+        &mut self,
+        frames: u32,
+        params: &[clap_event_param_value],
+        midi: &[clap_event_midi],
+        output: &mut OutputList,
+    ) -> Result<()> {
+        // the parameter changes all sit at the start of the block
+        let headers: Vec<*const clap_event_header> = params
+            .iter()
+            .map(|event| &event.header as *const _)
+            .chain(midi.iter().map(|event| &event.header as *const _))
+            .collect();
+
+        let in_events = clap_input_events {
+            ctx: &headers as *const _ as *mut c_void,
+            size: Some(input_size),
+            get: Some(input_get),
+        };
+
+        let out_events = clap_output_events {
+            ctx: output as *mut OutputList as *mut c_void,
+            try_push: Some(output_push),
+        };
+
+        let process = clap_process {
+            steady_time: self.steps,
+            frames_count: frames,
+            transport: ptr::null(),
+            audio_inputs: ptr::null(),
+            audio_outputs: ptr::null_mut(),
+            audio_inputs_count: 0,
+            audio_outputs_count: 0,
+            in_events: &in_events,
+            out_events: &out_events,
+        };
+
+        self.steps += frames as i64;
+
+        let status = unsafe {
+            match (*self.plugin).process {
+                Some(process_fn) => process_fn(self.plugin, &process),
+                None => CLAP_PROCESS_ERROR,
+            }
+        };
+
+        if status == CLAP_PROCESS_ERROR {
+            Err(Error::Plugin("process"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for PluginInstance<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            let plugin = &*self.plugin;
+
+            if let Some(stop_processing) = plugin.stop_processing {
+                stop_processing(self.plugin);
+            }
+
+            if let Some(deactivate) = plugin.deactivate {
+                deactivate(self.plugin);
+            }
+
+            if let Some(destroy) = plugin.destroy {
+                destroy(self.plugin);
+            }
+        }
+    }
+}
+
+fn header(size: usize, time: u32, type_: u16) -> clap_event_header {
+    clap_event_header {
+        size: size as u32,
+        time,
+        space_id: CLAP_CORE_EVENT_SPACE_ID,
+        type_,
+        flags: 0,
+    }
+}
+
+fn midi_event(time: u32, data: [u8; 3]) -> clap_event_midi {
+    clap_event_midi {
+        header: header(mem::size_of::<clap_event_midi>(), time, CLAP_EVENT_MIDI),
+        port_index: 0,
+        data,
+    }
+}
+
+fn param_event(param_id: clap_id, value: f64) -> clap_event_param_value {
+    clap_event_param_value {
+        header: header(
+            mem::size_of::<clap_event_param_value>(),
+            0,
+            CLAP_EVENT_PARAM_VALUE,
+        ),
+        param_id,
+        cookie: ptr::null_mut(),
+        note_id: -1,
+        port_index: -1,
+        channel: -1,
+        key: -1,
+        value,
+    }
+}
+
+#[derive(Default)]
+struct OutputList {
+    events: Vec<MidiEvent>,
+    offset: u64,
+}
+
+/// The MIDI 1.0 message for an event sent by the plugin.
+unsafe fn to_midi(event: *const clap_event_header) -> Option<[u8; 3]> {
+    let header = unsafe { &*event };
+
+    if header.space_id != CLAP_CORE_EVENT_SPACE_ID {
+        return None;
+    }
+
+    match header.type_ {
+        CLAP_EVENT_MIDI => Some(unsafe { (*(event as *const clap_event_midi)).data }),
+        CLAP_EVENT_NOTE_ON | CLAP_EVENT_NOTE_OFF | CLAP_EVENT_NOTE_CHOKE => {
+            let note = unsafe { &*(event as *const clap_event_note) };
+            let velocity = (note.velocity * 127.0).round().clamp(0.0, 127.0) as u8;
+
+            let (status, velocity) = match header.type_ {
+                CLAP_EVENT_NOTE_ON => (0x90, velocity.max(1)),
+                CLAP_EVENT_NOTE_OFF => (0x80, velocity),
+                _ => (0x80, 0),
+            };
+
+            Some([
+                status | (note.channel as u8 & 0x0F),
+                note.key as u8 & 0x7F,
+                velocity,
+            ])
+        }
+        CLAP_EVENT_NOTE_EXPRESSION => {
+            let expression = unsafe { &*(event as *const clap_event_note_expression) };
+            let pressure = (expression.value * 127.0).round().clamp(0.0, 127.0) as u8;
+
+            (expression.expression_id == CLAP_NOTE_EXPRESSION_PRESSURE).then_some([
+                0xA0 | (expression.channel as u8 & 0x0F),
+                expression.key as u8 & 0x7F,
+                pressure,
+            ])
+        }
+        _ => None,
+    }
+}
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const clap_host,
+    _extension_id: *const c_char,
+) -> *const c_void {
+    ptr::null()
+}
+
+unsafe extern "C" fn host_request(_host: *const clap_host) {}
+
+unsafe extern "C" fn input_size(list: *const clap_input_events) -> u32 {
+    let headers = unsafe { &*((*list).ctx as *const Vec<*const clap_event_header>) };
+    headers.len() as u32
+}
+
+unsafe extern "C" fn input_get(
+    list: *const clap_input_events,
+    index: u32,
+) -> *const clap_event_header {
+    let headers = unsafe { &*((*list).ctx as *const Vec<*const clap_event_header>) };
+    headers.get(index as usize).copied().unwrap_or(ptr::null())
+}
+
+unsafe extern "C" fn output_push(
+    list: *const clap_output_events,
+    event: *const clap_event_header,
+) -> bool {
+    unsafe {
+        let output = &mut *((*list).ctx as *mut OutputList);
+
+        if let Some(data) = to_midi(event) {
+            output.events.push(MidiEvent {
+                sample: output.offset + (*event).time as u64,
+                data,
+            });
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_byte_messages() {
+        let program = MidiEvent::new(0, &[0xC1, 5]);
+        let note = MidiEvent::new(0, &[0x91, 60, 100]);
+
+        assert_eq!(program.bytes(), [0xC1, 5]);
+        assert_eq!(note.bytes(), [0x91, 60, 100]);
+    }
+}
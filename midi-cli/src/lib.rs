@@ -0,0 +1,103 @@
+//! Runs Standard MIDI Files through the plugins without a host.
+//!
+//! The plugins are loaded from their CLAP binaries and driven through the CLAP API, so the
+//! processing is exactly the one a host gets, including the parameter smoothing and the order in
+//! which the plugins see parameter changes and events.
+
+mod automation;
+mod check;
+mod error;
+mod golden;
+mod host;
+mod smf;
+#[cfg(feature = "proptest")]
+pub mod stuck_notes;
+
+pub use automation::{Automation, Point, Position, value_text};
+pub use check::{Performance, check_notes};
+pub use error::{Error, Result};
+pub use golden::{assert_golden, golden_case};
+pub use host::{ClapLibrary, MidiEvent, ParamChange, ParamInfo, PluginInstance, built_plugin};
+pub use smf::{Meta, Song, TempoMap};
+
+/// Settings of an offline run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Render {
+    pub sample_rate: f64,
+    pub block_size: u32,
+
+    /// Seconds processed after the last event, for the plugins that send events once a fade or
+    /// a deferred switch ends.
+    pub tail: f64,
+}
+
+impl Default for Render {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48_000.0,
+            block_size: 512,
+            tail: 2.0,
+        }
+    }
+}
+
+impl Render {
+    /// Runs `song` through the plugin of `library`, `automation` applied on the way. The result
+    /// keeps the timing and the tempo map of `song`.
+    pub fn run(&self, library: &ClapLibrary, song: &Song, automation: &Automation) -> Result<Song> {
+        let mut plugin = PluginInstance::new(library, self.sample_rate, self.block_size)?;
+        let map = song.tempo_map();
+        let sample = |tick| (map.seconds(tick) * self.sample_rate).round() as u64;
+
+        let mut changes = Vec::new();
+
+        for (param, value) in &automation.set {
+            let (id, value) = plugin.param_value(param, &value_text(value))?;
+            changes.push(ParamChange {
+                sample: 0,
+                id,
+                value,
+            });
+        }
+
+        for point in &automation.automation {
+            let (id, value) = plugin.param_value(&point.param, &value_text(&point.value))?;
+            changes.push(ParamChange {
+                sample: sample(point.position.tick(&map)?),
+                id,
+                value,
+            });
+        }
+
+        changes.sort_by_key(|change| change.sample);
+
+        let events: Vec<MidiEvent> = song
+            .events
+            .iter()
+            .map(|(tick, data)| MidiEvent {
+                sample: sample(*tick),
+                data: *data,
+            })
+            .collect();
+
+        let last = events
+            .last()
+            .map(|event| event.sample)
+            .into_iter()
+            .chain(changes.last().map(|change| change.sample))
+            .max()
+            .unwrap_or_default();
+
+        let length = last + (self.tail * self.sample_rate).round() as u64 + 1;
+        let output = plugin.process(&events, &changes, length)?;
+
+        Ok(Song {
+            timing: song.timing,
+            meta: song.meta.clone(),
+            events: output
+                .into_iter()
+                .map(|event| (map.tick(event.sample as f64 / self.sample_rate), event.data))
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,127 @@
+use midi_cli::{Automation, ClapLibrary, PluginInstance, Render, Song};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: midi-cli <plugin.clap> <in.mid> <out.mid> [options]
+       midi-cli <plugin.clap> --list-params
+
+options:
+    --automation <file>    parameter changes, JSON for a .json file or a script otherwise
+    --set <name>=<value>   a parameter value for the whole run, repeatable
+    --sample-rate <hz>     default 48000
+    --block-size <frames>  default 512
+    --tail <seconds>       processing after the last event, default 2";
+
+struct Args {
+    plugin: PathBuf,
+    files: Option<(PathBuf, PathBuf)>,
+    automation: Automation,
+    render: Render,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut paths = Vec::new();
+    let mut automation = None;
+    let mut set = Vec::new();
+    let mut render = Render::default();
+    let mut list = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+
+        match arg.as_str() {
+            "--automation" => automation = Some(PathBuf::from(value(&arg)?)),
+            "--set" => set.push(value(&arg)?),
+            "--sample-rate" => {
+                render.sample_rate = value(&arg)?.parse().map_err(|_| "invalid sample rate")?
+            }
+            "--block-size" => {
+                render.block_size = value(&arg)?.parse().map_err(|_| "invalid block size")?
+            }
+            "--tail" => render.tail = value(&arg)?.parse().map_err(|_| "invalid tail")?,
+            "--list-params" => list = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let mut automation = match automation {
+        Some(path) => Automation::load(&path).map_err(|error| error.to_string())?,
+        None => Automation::default(),
+    };
+
+    for setting in set {
+        let (name, value) = setting
+            .split_once('=')
+            .ok_or(format!("--set {setting} is not <name>=<value>"))?;
+
+        automation.set.insert(
+            name.trim().to_owned(),
+            Value::String(value.trim().to_owned()),
+        );
+    }
+
+    let mut paths = paths.into_iter();
+
+    match (paths.next(), paths.next(), paths.next(), paths.next(), list) {
+        (Some(plugin), None, None, None, true) => Ok(Args {
+            plugin,
+            files: None,
+            automation,
+            render,
+        }),
+        (Some(plugin), Some(input), Some(output), None, false) => Ok(Args {
+            plugin,
+            files: Some((input, output)),
+            automation,
+            render,
+        }),
+        _ => Err(String::new()),
+    }
+}
+
+fn run(args: Args) -> midi_cli::Result<()> {
+    let library = ClapLibrary::load(&args.plugin)?;
+
+    let Some((input, output)) = args.files else {
+        let plugin =
+            PluginInstance::new(&library, args.render.sample_rate, args.render.block_size)?;
+
+        for param in plugin.params() {
+            println!("{}", param.name);
+        }
+
+        return Ok(());
+    };
+
+    let song = Song::load(&input)?;
+    args.render
+        .run(&library, &song, &args.automation)?
+        .save(&output)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{message}\n");
+            }
+
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
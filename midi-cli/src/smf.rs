@@ -0,0 +1,335 @@
+use crate::Result;
+use midly::{
+    Format, Header, MetaMessage, Smf, Timing, Track, TrackEvent, TrackEventKind,
+    live::LiveEvent,
+    num::{u24, u28},
+};
+use std::path::Path;
+
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// The meta events the processing keeps, the others do not reach the plugins and are dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Meta {
+    /// Microseconds per quarter note.
+    Tempo(u32),
+
+    /// Numerator, power of two of the denominator, MIDI clocks per click and 32nd notes per
+    /// quarter note, as in the file.
+    TimeSignature(u8, u8, u8, u8),
+}
+
+/// A Standard MIDI File with its tracks merged, the events at absolute ticks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Song {
+    pub timing: Timing,
+    pub meta: Vec<(u64, Meta)>,
+    pub events: Vec<(u64, [u8; 3])>,
+}
+
+impl Song {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let smf = Smf::parse(bytes)?;
+        let mut meta = Vec::new();
+        let mut events = Vec::new();
+
+        for track in &smf.tracks {
+            let mut tick = 0;
+
+            for event in track {
+                tick += event.delta.as_int() as u64;
+
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                        meta.push((tick, Meta::Tempo(tempo.as_int())));
+                    }
+                    TrackEventKind::Meta(MetaMessage::TimeSignature(num, denom, clocks, notes)) => {
+                        meta.push((tick, Meta::TimeSignature(num, denom, clocks, notes)));
+                    }
+                    TrackEventKind::Midi { .. } => {
+                        if let Some(live) = event.kind.as_live_event() {
+                            let mut data = Vec::with_capacity(3);
+                            let mut bytes = [0; 3];
+
+                            live.write_std(&mut data)?;
+                            let length = data.len().min(3);
+                            bytes[..length].copy_from_slice(&data[..length]);
+                            events.push((tick, bytes));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        // stable, events of the same tick keep their order within their track
+        meta.sort_by_key(|(tick, _)| *tick);
+        events.sort_by_key(|(tick, _)| *tick);
+
+        Ok(Self {
+            timing: smf.header.timing,
+            meta,
+            events,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// A single track file, the meta events first on their tick.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut timed: Vec<(u64, TrackEventKind)> = Vec::new();
+
+        for (tick, meta) in &self.meta {
+            let message = match *meta {
+                Meta::Tempo(tempo) => MetaMessage::Tempo(u24::new(tempo)),
+                Meta::TimeSignature(num, denom, clocks, notes) => {
+                    MetaMessage::TimeSignature(num, denom, clocks, notes)
+                }
+            };
+
+            timed.push((*tick, TrackEventKind::Meta(message)));
+        }
+
+        for (tick, data) in &self.events {
+            let length = match data[0] & 0xF0 {
+                0xC0 | 0xD0 => 2,
+                _ => 3,
+            };
+
+            if let LiveEvent::Midi { channel, message } = LiveEvent::parse(&data[..length])? {
+                timed.push((*tick, TrackEventKind::Midi { channel, message }));
+            }
+        }
+
+        timed.sort_by_key(|(tick, _)| *tick);
+
+        let mut track: Track = Vec::with_capacity(timed.len() + 1);
+        let mut last = 0;
+
+        for (tick, kind) in timed {
+            track.push(TrackEvent {
+                delta: u28::new((tick - last) as u32),
+                kind,
+            });
+            last = tick;
+        }
+
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let mut smf = Smf::new(Header::new(Format::SingleTrack, self.timing));
+        let mut bytes = Vec::new();
+
+        smf.tracks.push(track);
+        smf.write_std(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        Ok(std::fs::write(path, self.to_bytes()?)?)
+    }
+
+    pub fn tempo_map(&self) -> TempoMap {
+        TempoMap::new(self.timing, &self.meta)
+    }
+
+    /// The channel events as text, one per line with its tick and its channel counted from 1:
+    ///
+    /// ```text
+    /// 0 ch1 note-on 60 100
+    /// 480 ch1 cc 64 127
+    /// ```
+    pub fn event_log(&self) -> String {
+        self.events
+            .iter()
+            .map(|(tick, data)| format!("{tick} ch{} {}\n", (data[0] & 0x0F) + 1, message(data)))
+            .collect()
+    }
+}
+
+fn message(data: &[u8; 3]) -> String {
+    let [status, a, b] = *data;
+
+    match status & 0xF0 {
+        0x80 => format!("note-off {a} {b}"),
+        0x90 => format!("note-on {a} {b}"),
+        0xA0 => format!("poly-pressure {a} {b}"),
+        0xB0 => format!("cc {a} {b}"),
+        0xC0 => format!("program {a}"),
+        0xD0 => format!("pressure {a}"),
+        0xE0 => format!("pitch-bend {}", ((b as u16) << 7) | a as u16),
+        _ => format!("{status:#04x} {a} {b}"),
+    }
+}
+
+/// Converts between ticks, seconds and bars.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    timing: Timing,
+
+    /// The tick, the seconds at the tick and the tempo from the tick on.
+    tempos: Vec<(u64, f64, u32)>,
+
+    /// The tick, the bar at the tick, numerator and denominator from the tick on.
+    signatures: Vec<(u64, u64, u8, u8)>,
+}
+
+impl TempoMap {
+    pub fn new(timing: Timing, meta: &[(u64, Meta)]) -> Self {
+        let mut map = Self {
+            timing,
+            tempos: vec![(0, 0.0, DEFAULT_TEMPO)],
+            signatures: vec![(0, 0, 4, 4)],
+        };
+
+        for &(tick, meta) in meta {
+            match meta {
+                Meta::Tempo(tempo) => {
+                    let seconds = map.seconds(tick);
+
+                    map.tempos.retain(|(start, ..)| *start < tick);
+                    map.tempos.push((tick, seconds, tempo));
+                }
+                Meta::TimeSignature(num, denom, ..) => {
+                    let Some(ppq) = map.ppq() else { continue };
+                    let (start, bar, last_num, last_denom) = *map.signatures.last().unwrap();
+                    let bar_ticks = bar_ticks(ppq, last_num, last_denom);
+                    // a signature in the middle of a bar starts a new one
+                    let bar = bar + (tick - start).div_ceil(bar_ticks.max(1));
+
+                    map.signatures.retain(|(start, ..)| *start < tick);
+                    map.signatures.push((
+                        tick,
+                        bar,
+                        num.max(1),
+                        1u8.checked_shl(denom as u32).unwrap_or(4),
+                    ));
+                }
+            }
+        }
+
+        map
+    }
+
+    fn ppq(&self) -> Option<u64> {
+        match self.timing {
+            Timing::Metrical(ppq) => Some(ppq.as_int().max(1) as u64),
+            Timing::Timecode(..) => None,
+        }
+    }
+
+    pub fn seconds(&self, tick: u64) -> f64 {
+        match self.timing {
+            Timing::Metrical(ppq) => {
+                let &(start, seconds, tempo) = self
+                    .tempos
+                    .iter()
+                    .rev()
+                    .find(|(start, ..)| *start <= tick)
+                    .unwrap_or(&self.tempos[0]);
+
+                seconds
+                    + (tick - start) as f64 * tempo as f64
+                        / 1_000_000.0
+                        / ppq.as_int().max(1) as f64
+            }
+            Timing::Timecode(fps, subframes) => {
+                tick as f64 / (fps.as_f32() as f64 * subframes.max(1) as f64)
+            }
+        }
+    }
+
+    pub fn tick(&self, seconds: f64) -> u64 {
+        let seconds = seconds.max(0.0);
+
+        match self.timing {
+            Timing::Metrical(ppq) => {
+                let &(start, start_seconds, tempo) = self
+                    .tempos
+                    .iter()
+                    .rev()
+                    .find(|(_, start, _)| *start <= seconds)
+                    .unwrap_or(&self.tempos[0]);
+
+                start
+                    + ((seconds - start_seconds) * 1_000_000.0 * ppq.as_int().max(1) as f64
+                        / tempo as f64)
+                        .round() as u64
+            }
+            Timing::Timecode(fps, subframes) => {
+                (seconds * fps.as_f32() as f64 * subframes.max(1) as f64).round() as u64
+            }
+        }
+    }
+
+    /// The tick of a beat of a bar, both counted from 1 as sequencers show them. `None` for a
+    /// file timed in SMPTE frames, which has no bars.
+    pub fn bar(&self, bar: u64, beat: f64) -> Option<u64> {
+        let ppq = self.ppq()?;
+        let bar = bar.checked_sub(1)?;
+        let &(start, first, num, denom) = self
+            .signatures
+            .iter()
+            .rev()
+            .find(|(_, first, ..)| *first <= bar)?;
+
+        let beat_ticks = ppq as f64 * 4.0 / denom as f64;
+        let beat = (beat - 1.0).max(0.0);
+
+        Some(
+            start + (bar - first) * bar_ticks(ppq, num, denom) + (beat * beat_ticks).round() as u64,
+        )
+    }
+}
+
+fn bar_ticks(ppq: u64, num: u8, denom: u8) -> u64 {
+    ppq * 4 * num as u64 / denom.max(1) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::num::u15;
+
+    #[test]
+    fn bars_follow_time_signatures() {
+        let timing = Timing::Metrical(u15::new(480));
+        // 3/4 from bar 3
+        let map = TempoMap::new(timing, &[(3840, Meta::TimeSignature(3, 2, 24, 8))]);
+
+        assert_eq!(map.bar(1, 1.0), Some(0));
+        assert_eq!(map.bar(2, 3.0), Some(1920 + 960));
+        assert_eq!(map.bar(3, 1.0), Some(3840));
+        assert_eq!(map.bar(5, 1.0), Some(3840 + 2 * 1440));
+    }
+
+    #[test]
+    fn seconds_follow_tempos() {
+        let timing = Timing::Metrical(u15::new(480));
+        let map = TempoMap::new(timing, &[(960, Meta::Tempo(250_000))]);
+
+        assert_eq!(map.seconds(960), 1.0);
+        assert_eq!(map.seconds(1440), 1.25);
+        assert_eq!(map.tick(1.25), 1440);
+        assert_eq!(map.tick(0.5), 480);
+    }
+
+    #[test]
+    fn round_trip() {
+        let song = Song {
+            timing: Timing::Metrical(u15::new(96)),
+            meta: vec![(0, Meta::Tempo(600_000))],
+            events: vec![(0, [0xC0, 3, 0]), (0, [0x90, 60, 100]), (96, [0x80, 60, 0])],
+        };
+
+        assert_eq!(Song::parse(&song.to_bytes().unwrap()).unwrap(), song);
+        assert_eq!(
+            song.event_log(),
+            "0 ch1 program 3\n0 ch1 note-on 60 100\n96 ch1 note-off 60 0\n"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! The stuck note property test of the plugin crates: a player presses and releases keys, moves
+//! controllers and pedals while the parameters of the plugin change, and every note sent must be
+//! released, see [`check_notes`].
+
+use crate::{ClapLibrary, MidiEvent, Performance, built_plugin, check_notes};
+use proptest::prelude::*;
+use proptest::test_runner::TestRunner;
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    Wait(u64),
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+    },
+    Controller {
+        channel: u8,
+        cc: u8,
+        value: u8,
+    },
+    Pedal {
+        channel: u8,
+        down: bool,
+    },
+    /// A parameter set by name to a value written as it shows it.
+    Param(&'static str, String),
+}
+
+/// A few keys around middle C and the ends of the range, so the same notes are often pressed
+/// twice and released in any order.
+pub fn note() -> impl Strategy<Value = u8> {
+    prop_oneof![Just(0), 58u8..66, Just(127)]
+}
+
+/// The actions of the player, mixed with the parameter changes of `param`.
+pub fn action(
+    param: impl Strategy<Value = (&'static str, String)>,
+) -> impl Strategy<Value = Action> {
+    prop_oneof![
+        3 => (0u64..2000).prop_map(Action::Wait),
+        4 => (0u8..3, note(), 1u8..128)
+            .prop_map(|(channel, note, velocity)| Action::NoteOn { channel, note, velocity }),
+        4 => (0u8..3, note()).prop_map(|(channel, note)| Action::NoteOff { channel, note }),
+        1 => (0u8..3, prop_oneof![Just(1u8), Just(7), Just(11)], 0u8..128)
+            .prop_map(|(channel, cc, value)| Action::Controller { channel, cc, value }),
+        1 => (0u8..3, any::<bool>()).prop_map(|(channel, down)| Action::Pedal { channel, down }),
+        2 => param.prop_map(|(name, value)| Action::Param(name, value)),
+    ]
+}
+
+pub fn play(library: &ClapLibrary, actions: &[Action]) -> Vec<MidiEvent> {
+    let mut performance = Performance::new(library).unwrap();
+
+    for action in actions {
+        match *action {
+            Action::Wait(samples) => performance.wait(samples),
+            Action::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => performance.note_on(channel, note, velocity),
+            Action::NoteOff { channel, note } => performance.note_off(channel, note),
+            Action::Controller { channel, cc, value } => performance.controller(channel, cc, value),
+            Action::Pedal { channel, down } => {
+                performance.controller(channel, 64, if down { 127 } else { 0 })
+            }
+            Action::Param(name, ref value) => performance.param(name, value).unwrap(),
+        }
+    }
+
+    performance.finish().unwrap()
+}
+
+/// Plays random performances through the plugin built by the crate `plugin`, its parameters
+/// changed by `param`, and panics when a note is left sounding or released without being opened.
+pub fn assert_no_stuck_notes(plugin: &str, param: impl Strategy<Value = (&'static str, String)>) {
+    let library = ClapLibrary::load(&built_plugin(plugin).unwrap()).unwrap();
+    let actions = prop::collection::vec(action(param), 1..80);
+
+    let result = TestRunner::default().run(&actions, |actions| {
+        let output = play(&library, &actions);
+        prop_assert_eq!(check_notes(&output), Ok(()));
+        Ok(())
+    });
+
+    if let Err(error) = result {
+        panic!("{plugin}: {error}");
+    }
+}
@@ -0,0 +1,130 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Offset between the MSB controller of a 14-bit pair and its LSB.
+const LSB_OFFSET: u8 = 32;
+
+/// A raw 7-bit control change message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlChange {
+    pub channel: u8,
+    pub cc: u8,
+    pub value: u8,
+}
+
+impl ControlChange {
+    pub fn new(channel: u8, cc: u8, value: u8) -> Self {
+        Self {
+            channel: channel & 0x0F,
+            cc: cc & 0x7F,
+            value: value & 0x7F,
+        }
+    }
+
+    /// The three bytes of the message as they go on the wire.
+    pub fn to_bytes(self) -> [u8; 3] {
+        [0xB0 | self.channel, self.cc, self.value]
+    }
+
+    pub fn to_event(self, timing: u32) -> NoteEvent<()> {
+        NoteEvent::MidiCC {
+            timing,
+            channel: self.channel,
+            cc: self.cc,
+            value: self.value as f32 / 127.0,
+        }
+    }
+}
+
+/// The value of a controller slot, either a single 7-bit message or a 14-bit MSB / LSB pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControllerValue {
+    Coarse(u8),
+    Fine(u16),
+}
+
+impl ControllerValue {
+    /// The 7-bit value, the MSB of a 14-bit value.
+    pub fn coarse(self) -> u8 {
+        match self {
+            Self::Coarse(value) => value,
+            Self::Fine(value) => (value >> 7) as u8,
+        }
+    }
+
+    /// Messages for the controller `msb_cc`, the MSB always goes first because receivers
+    /// reset the LSB when a new MSB arrives. Controllers without a LSB only send the MSB.
+    pub fn messages(self, channel: u8, msb_cc: u8) -> impl Iterator<Item = ControlChange> {
+        let (msb, lsb) = match self {
+            Self::Coarse(value) => (value, None),
+            Self::Fine(value) => ((value >> 7) as u8, Some(value as u8)),
+        };
+
+        let lsb = lsb
+            .zip(lsb_controller(msb_cc))
+            .map(|(value, cc)| ControlChange::new(channel, cc, value));
+
+        std::iter::once(ControlChange::new(channel, msb_cc, msb)).chain(lsb)
+    }
+}
+
+/// The LSB controller paired with `msb_cc`, only controllers 0 to 31 have one.
+pub fn lsb_controller(msb_cc: u8) -> Option<u8> {
+    (msb_cc < LSB_OFFSET).then_some(msb_cc + LSB_OFFSET)
+}
+
+/// The controller a message belongs to, LSB controllers 32 to 63 report their MSB.
+pub fn logical_controller(cc: u8) -> u8 {
+    if (LSB_OFFSET..2 * LSB_OFFSET).contains(&cc) {
+        cc - LSB_OFFSET
+    } else {
+        cc
+    }
+}
+
+/// Whether `cc` is the LSB half of a 14-bit pair.
+pub fn is_lsb_controller(cc: u8) -> bool {
+    logical_controller(cc) != cc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(value: ControllerValue, channel: u8, cc: u8) -> Vec<[u8; 3]> {
+        value
+            .messages(channel, cc)
+            .map(ControlChange::to_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn fine_sends_msb_then_lsb() {
+        assert_eq!(
+            bytes(ControllerValue::Fine(0x2005), 0, 1),
+            [[0xB0, 1, 0x40], [0xB0, 33, 0x05]]
+        );
+    }
+
+    #[test]
+    fn coarse_sends_one_message() {
+        assert_eq!(bytes(ControllerValue::Coarse(100), 3, 7), [[0xB3, 7, 100]]);
+    }
+
+    #[test]
+    fn fine_without_lsb_controller_sends_msb() {
+        assert_eq!(
+            bytes(ControllerValue::Fine(0x3FFF), 0, 74),
+            [[0xB0, 74, 0x7F]]
+        );
+    }
+
+    #[test]
+    fn pairs_map_to_msb() {
+        assert_eq!(logical_controller(43), 11);
+        assert_eq!(logical_controller(11), 11);
+        assert_eq!(logical_controller(64), 64);
+        assert!(is_lsb_controller(33));
+        assert!(!is_lsb_controller(1));
+    }
+}
@@ -0,0 +1,557 @@
+use crate::monitor::{EventMonitor, MonitorEntry, export_monitor};
+use crate::overlay::StateOverlay;
+use crate::preset::Preset;
+use crate::program::{ProgramMap, WithProgramMap};
+use crate::setup::{Setup, SetupField};
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use nih_plug_vizia::{ViziaTheming, assets, create_vizia_editor};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use nih_plug_vizia::ViziaState;
+
+/// The lines kept by the monitor, the oldest are dropped first.
+const HISTORY: usize = 2000;
+
+pub fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (640, 460))
+}
+
+/// The state of a switching plugin as shown by the editor.
+struct StateFrame {
+    state: &'static str,
+    keys: Keys,
+    damper: bool,
+    snapshot: Vec<String>,
+}
+
+/// Reads the last state published by the audio thread, formatted for the editor.
+type StatePoll = Arc<dyn Fn() -> Option<StateFrame> + Send + Sync>;
+
+/// The keys held on any channel, those held through a switch apart.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Keys {
+    held: u128,
+    switched: u128,
+}
+
+impl Data for Keys {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+#[derive(Lens)]
+struct EditorData {
+    #[lens(ignore)]
+    monitor: Arc<EventMonitor>,
+    #[lens(ignore)]
+    entries: Vec<MonitorEntry>,
+    #[lens(ignore)]
+    name: &'static str,
+    #[lens(ignore)]
+    poll_state: Option<StatePoll>,
+    #[lens(ignore)]
+    gui: Arc<dyn GuiContext>,
+    #[lens(ignore)]
+    params: Arc<dyn Params>,
+    #[lens(ignore)]
+    presets: Vec<Preset>,
+    #[lens(ignore)]
+    fields: Vec<SetupField>,
+    #[lens(ignore)]
+    preset_index: Option<usize>,
+    #[lens(ignore)]
+    program_map: Option<Arc<dyn WithProgramMap>>,
+    lines: Vec<String>,
+    preset: String,
+    setup_path: String,
+    recording: bool,
+    status: String,
+    state: String,
+    keys: Keys,
+    snapshot: Vec<String>,
+    map_entry: String,
+    map_lines: Vec<String>,
+}
+
+enum EditorEvent {
+    AddMapEntry,
+    Clear,
+    Export,
+    ExportSetup,
+    ImportSetup,
+    NextPreset,
+    Poll,
+    PreviousPreset,
+    RemoveMapEntry,
+    SetMapEntry(String),
+    SetSetupPath(String),
+    ToggleRecording,
+}
+
+impl EditorData {
+    /// Loads the factory preset `offset` places from the current one.
+    fn step_preset(&mut self, offset: isize) {
+        let count = self.presets.len() as isize;
+
+        if count == 0 {
+            return;
+        }
+
+        let index = match self.preset_index {
+            Some(index) => (index as isize + offset).rem_euclid(count) as usize,
+            None if offset < 0 => count as usize - 1,
+            None => 0,
+        };
+        let preset = &self.presets[index];
+
+        match preset.state(&*self.params, self.gui.get_state()) {
+            Ok(state) => {
+                self.gui.set_state(state);
+                self.preset_index = Some(index);
+                self.preset = preset.name.clone();
+            }
+            Err(error) => self.status = error,
+        }
+    }
+
+    /// Adds the typed entry to the program map, or removes the entry of its incoming program.
+    fn edit_program_map(&mut self, add: bool) {
+        let Some(owner) = &self.program_map else {
+            return;
+        };
+
+        self.status = match ProgramMap::parse_entry(&self.map_entry) {
+            Ok((program, target)) => match owner.program_map().write() {
+                Ok(mut map) if add => match target {
+                    Some(target) => {
+                        map.insert(program, target);
+                        format!("Program {program} mapped to {target}")
+                    }
+                    None => String::from("Add needs the bank MSB, LSB and program"),
+                },
+                Ok(mut map) => {
+                    map.remove(program);
+                    format!("Program {program} unmapped")
+                }
+                Err(_) => String::from("The program map is unavailable"),
+            },
+            Err(error) => error,
+        };
+    }
+}
+
+impl Model for EditorData {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|event, _| match event {
+            EditorEvent::Clear => {
+                self.entries.clear();
+                self.lines.clear();
+            }
+            EditorEvent::Export => {
+                let path = export_dir().join(file_name(self.name, "monitor.txt"));
+
+                self.status = match export_monitor(&self.entries, self.monitor.sample_rate(), &path)
+                {
+                    Ok(()) => format!("Exported to {}", path.display()),
+                    Err(error) => format!("Export failed: {error}"),
+                };
+            }
+            EditorEvent::ExportSetup => {
+                let setup = Setup::from_state(self.name, self.gui.get_state());
+
+                self.status = match setup.save(self.setup_path.as_ref()) {
+                    Ok(()) => format!("Setup exported to {}", self.setup_path),
+                    Err(error) => format!("Setup export failed: {error}"),
+                };
+            }
+            EditorEvent::ImportSetup => {
+                let state = Setup::load(self.setup_path.as_ref())
+                    .and_then(|setup| setup.into_state(self.name, &*self.params, &self.fields));
+
+                self.status = match state {
+                    Ok(state) => {
+                        self.gui.set_state(state);
+                        self.preset_index = None;
+                        self.preset = String::from("No preset");
+                        format!("Setup imported from {}", self.setup_path)
+                    }
+                    Err(error) => format!("{}: {error}", self.setup_path),
+                };
+            }
+            EditorEvent::SetSetupPath(path) => self.setup_path = path.clone(),
+            EditorEvent::SetMapEntry(entry) => self.map_entry = entry.clone(),
+            EditorEvent::AddMapEntry => self.edit_program_map(true),
+            EditorEvent::RemoveMapEntry => self.edit_program_map(false),
+            EditorEvent::NextPreset => self.step_preset(1),
+            EditorEvent::PreviousPreset => self.step_preset(-1),
+            EditorEvent::Poll => {
+                if let Some(frame) = self.poll_state.as_ref().and_then(|poll| poll()) {
+                    let damper = if frame.damper {
+                        "damper down"
+                    } else {
+                        "damper up"
+                    };
+
+                    self.state = format!("{}, {damper}", frame.state);
+                    self.keys = frame.keys;
+                    self.snapshot = frame.snapshot;
+                }
+
+                // the map also changes when the host or a setup loads a state
+                if let Some(Ok(map)) = self
+                    .program_map
+                    .as_ref()
+                    .map(|owner| owner.program_map().try_read())
+                {
+                    self.map_lines = map
+                        .iter()
+                        .map(|(program, target)| format!("{program:3} → {target}"))
+                        .collect();
+                }
+
+                let sample_rate = self.monitor.sample_rate();
+                let start = self.entries.len();
+
+                self.entries.extend(self.monitor.drain());
+
+                if self.entries.len() > start {
+                    self.lines.extend(
+                        self.entries[start..]
+                            .iter()
+                            .map(|entry| entry.line(sample_rate)),
+                    );
+
+                    let excess = self.entries.len().saturating_sub(HISTORY);
+                    self.entries.drain(..excess);
+                    self.lines.drain(..excess);
+                }
+            }
+            EditorEvent::ToggleRecording => {
+                self.recording = !self.recording;
+                self.monitor.set_recording(self.recording);
+            }
+        });
+    }
+}
+
+/// The file of the plugin `name` for `what`, such as `program-change-monitor.txt`.
+fn file_name(name: &str, what: &str) -> String {
+    format!("{}-{what}", name.to_lowercase().replace(' ', "-"))
+}
+
+/// The home directory, where the exports go, the working directory when unknown.
+fn export_dir() -> std::path::PathBuf {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(Into::into)
+        .unwrap_or_default()
+}
+
+/// A strip of the 128 keys, the held keys lit and those held through a switch in another color.
+struct KeyStrip<L> {
+    keys: L,
+}
+
+impl<L: Lens<Target = Keys>> KeyStrip<L> {
+    fn new(cx: &mut Context, keys: L) -> Handle<'_, Self> {
+        Self { keys }
+            .build(cx, |_| {})
+            .bind(keys, |handle, _| handle.needs_redraw())
+    }
+}
+
+impl<L: Lens<Target = Keys>> View for KeyStrip<L> {
+    fn element(&self) -> Option<&'static str> {
+        Some("key-strip")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        let keys = self.keys.get(cx);
+        let width = bounds.w / 128.0;
+
+        for note in 0..128 {
+            let mask = 1u128 << note;
+            let black = matches!(note % 12, 1 | 3 | 6 | 8 | 10);
+
+            let color = if keys.switched & mask != 0 {
+                vg::Color::rgb(230, 150, 40)
+            } else if keys.held & mask != 0 {
+                vg::Color::rgb(70, 190, 90)
+            } else if black {
+                vg::Color::rgb(40, 40, 40)
+            } else {
+                vg::Color::rgb(235, 235, 235)
+            };
+            let height = if black { bounds.h * 0.65 } else { bounds.h };
+
+            let mut path = vg::Path::new();
+            path.rect(
+                bounds.x + note as f32 * width,
+                bounds.y,
+                width - 1.0,
+                height,
+            );
+            canvas.fill_path(&path, &vg::Paint::color(color));
+        }
+    }
+}
+
+/// An editor loading the factory `presets` of the plugin `name`, see [`Preset`], and showing
+/// the events it received and sent and its state transitions, recorded by `monitor` while the
+/// record button is on.
+pub fn create(
+    name: &'static str,
+    params: Arc<dyn Params>,
+    presets: &'static [&'static str],
+    monitor: Arc<EventMonitor>,
+    editor_state: Arc<ViziaState>,
+) -> Option<Box<dyn Editor>> {
+    build(name, params, presets, &[], monitor, None, editor_state)
+}
+
+/// The editor of [`create`] above the current state of a switching plugin: its state, the damper,
+/// the keys held and those held through a switch, and the parameters sent last. It also edits the
+/// program map of the plugin. A setup import checks the persisted `fields` of the plugin, the
+/// editor state aside.
+pub fn create_with_state<P, S>(
+    name: &'static str,
+    params: Arc<P>,
+    presets: &'static [&'static str],
+    fields: &'static [SetupField],
+    monitor: Arc<EventMonitor>,
+    overlay: Arc<StateOverlay<S>>,
+    editor_state: Arc<ViziaState>,
+) -> Option<Box<dyn Editor>>
+where
+    P: Params + WithProgramMap + 'static,
+    S: Debug + Send + 'static,
+{
+    let poll: StatePoll = Arc::new(move || {
+        let view = overlay.take()?;
+        let mask = |notes: &crate::NotesState| {
+            notes
+                .iter()
+                .fold(0u128, |mask, (_, note)| mask | (1u128 << note))
+        };
+
+        Some(StateFrame {
+            state: view.state,
+            keys: Keys {
+                held: mask(&view.notes),
+                switched: mask(&view.switched),
+            },
+            damper: !view.damper.is_off(),
+            snapshot: match view.snapshot {
+                Some(snapshot) => format!("{snapshot:#?}")
+                    .lines()
+                    .map(str::to_owned)
+                    .collect(),
+                None => Vec::new(),
+            },
+        })
+    });
+
+    let program_map: Arc<dyn WithProgramMap> = params.clone();
+
+    build(
+        name,
+        params,
+        presets,
+        fields,
+        monitor,
+        Some((poll, program_map)),
+        editor_state,
+    )
+}
+
+fn build(
+    name: &'static str,
+    params: Arc<dyn Params>,
+    presets: &'static [&'static str],
+    fields: &'static [SetupField],
+    monitor: Arc<EventMonitor>,
+    switching: Option<(StatePoll, Arc<dyn WithProgramMap>)>,
+    editor_state: Arc<ViziaState>,
+) -> Option<Box<dyn Editor>> {
+    // the presets are checked by the tests of the plugins, an invalid one is left out
+    let presets: Vec<Preset> = presets
+        .iter()
+        .filter_map(|json| {
+            Preset::parse(json)
+                .inspect_err(|error| nih_error!("{name}: {error}"))
+                .ok()
+        })
+        .collect();
+
+    let fields: Vec<SetupField> = fields
+        .iter()
+        .copied()
+        .chain([SetupField::of::<ViziaState>("editor-state")])
+        .collect();
+
+    create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, gui| {
+        assets::register_noto_sans_light(cx);
+
+        let has_presets = !presets.is_empty();
+        let has_state = switching.is_some();
+        let (poll_state, program_map) = switching.clone().unzip();
+
+        EditorData {
+            monitor: monitor.clone(),
+            entries: Vec::new(),
+            name,
+            poll_state,
+            gui,
+            params: params.clone(),
+            presets: presets.clone(),
+            fields: fields.clone(),
+            preset_index: None,
+            program_map,
+            lines: Vec::new(),
+            preset: String::from("No preset"),
+            setup_path: export_dir()
+                .join(file_name(name, "setup.json"))
+                .display()
+                .to_string(),
+            recording: monitor.is_recording(),
+            status: String::new(),
+            state: String::from("Off"),
+            keys: Keys::default(),
+            snapshot: Vec::new(),
+            map_entry: String::new(),
+            map_lines: Vec::new(),
+        }
+        .build(cx);
+
+        let timer = cx.add_timer(Duration::from_millis(50), None, |cx, action| {
+            if let TimerAction::Tick(_) = action {
+                cx.emit(EditorEvent::Poll);
+            }
+        });
+        cx.start_timer(timer);
+
+        VStack::new(cx, |cx| {
+            Label::new(cx, name).font_size(20.0).height(Pixels(28.0));
+
+            if has_presets {
+                HStack::new(cx, |cx| {
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(EditorEvent::PreviousPreset),
+                        |cx| Label::new(cx, "<"),
+                    );
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(EditorEvent::NextPreset),
+                        |cx| Label::new(cx, ">"),
+                    );
+                    Label::new(cx, EditorData::preset);
+                })
+                .height(Auto)
+                .col_between(Pixels(8.0));
+            }
+
+            if has_state {
+                Label::new(cx, EditorData::state);
+                KeyStrip::new(cx, EditorData::keys)
+                    .width(Stretch(1.0))
+                    .height(Pixels(36.0));
+            }
+
+            HStack::new(cx, |cx| {
+                Button::new(
+                    cx,
+                    |cx| cx.emit(EditorEvent::ToggleRecording),
+                    |cx| {
+                        Label::new(
+                            cx,
+                            EditorData::recording
+                                .map(|recording| if *recording { "Stop" } else { "Record" }),
+                        )
+                    },
+                );
+                Button::new(
+                    cx,
+                    |cx| cx.emit(EditorEvent::Clear),
+                    |cx| Label::new(cx, "Clear"),
+                );
+                Button::new(
+                    cx,
+                    |cx| cx.emit(EditorEvent::Export),
+                    |cx| Label::new(cx, "Export"),
+                );
+            })
+            .height(Auto)
+            .col_between(Pixels(8.0));
+
+            HStack::new(cx, |cx| {
+                Textbox::new(cx, EditorData::setup_path)
+                    .on_submit(|cx, path, _| cx.emit(EditorEvent::SetSetupPath(path)))
+                    .width(Stretch(1.0));
+                Button::new(
+                    cx,
+                    |cx| cx.emit(EditorEvent::ExportSetup),
+                    |cx| Label::new(cx, "Export Setup"),
+                );
+                Button::new(
+                    cx,
+                    |cx| cx.emit(EditorEvent::ImportSetup),
+                    |cx| Label::new(cx, "Import Setup"),
+                );
+            })
+            .height(Auto)
+            .col_between(Pixels(8.0));
+
+            if has_state {
+                HStack::new(cx, |cx| {
+                    Textbox::new(cx, EditorData::map_entry)
+                        .on_submit(|cx, entry, _| cx.emit(EditorEvent::SetMapEntry(entry)))
+                        .width(Stretch(1.0));
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(EditorEvent::AddMapEntry),
+                        |cx| Label::new(cx, "Map Program"),
+                    );
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(EditorEvent::RemoveMapEntry),
+                        |cx| Label::new(cx, "Unmap"),
+                    );
+                })
+                .height(Auto)
+                .col_between(Pixels(8.0));
+            }
+
+            Label::new(cx, EditorData::status);
+
+            HStack::new(cx, |cx| {
+                ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+                    List::new(cx, EditorData::lines, |cx, _, line| {
+                        Label::new(cx, line);
+                    });
+                });
+
+                if has_state {
+                    ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+                        List::new(cx, EditorData::snapshot, |cx, _, line| {
+                            Label::new(cx, line);
+                        });
+                        Label::new(cx, "Program map");
+                        List::new(cx, EditorData::map_lines, |cx, _, line| {
+                            Label::new(cx, line);
+                        });
+                    })
+                    .width(Pixels(220.0));
+                }
+            })
+            .col_between(Pixels(8.0));
+        })
+        .row_between(Pixels(6.0))
+        .child_space(Pixels(10.0));
+    })
+}
@@ -0,0 +1,84 @@
+use nih_plug::prelude::*;
+
+/// Moves the event to `channel`, events without a channel are returned as is.
+pub fn with_channel(mut event: NoteEvent<()>, channel: u8) -> NoteEvent<()> {
+    match &mut event {
+        NoteEvent::NoteOn { channel: c, .. }
+        | NoteEvent::NoteOff { channel: c, .. }
+        | NoteEvent::Choke { channel: c, .. }
+        | NoteEvent::VoiceTerminated { channel: c, .. }
+        | NoteEvent::PolyPressure { channel: c, .. }
+        | NoteEvent::PolyVolume { channel: c, .. }
+        | NoteEvent::PolyPan { channel: c, .. }
+        | NoteEvent::PolyTuning { channel: c, .. }
+        | NoteEvent::PolyVibrato { channel: c, .. }
+        | NoteEvent::PolyExpression { channel: c, .. }
+        | NoteEvent::PolyBrightness { channel: c, .. }
+        | NoteEvent::MidiChannelPressure { channel: c, .. }
+        | NoteEvent::MidiPitchBend { channel: c, .. }
+        | NoteEvent::MidiCC { channel: c, .. }
+        | NoteEvent::MidiProgramChange { channel: c, .. } => *c = channel,
+        _ => {}
+    }
+
+    event
+}
+
+/// The note of a per-note event.
+pub fn event_note(event: &NoteEvent<()>) -> Option<u8> {
+    match *event {
+        NoteEvent::NoteOn { note, .. }
+        | NoteEvent::NoteOff { note, .. }
+        | NoteEvent::Choke { note, .. }
+        | NoteEvent::VoiceTerminated { note, .. }
+        | NoteEvent::PolyPressure { note, .. }
+        | NoteEvent::PolyVolume { note, .. }
+        | NoteEvent::PolyPan { note, .. }
+        | NoteEvent::PolyTuning { note, .. }
+        | NoteEvent::PolyVibrato { note, .. }
+        | NoteEvent::PolyExpression { note, .. }
+        | NoteEvent::PolyBrightness { note, .. } => Some(note),
+        _ => None,
+    }
+}
+
+/// Moves a per-note event to `channel` and `note`, other events only get the channel.
+pub fn with_note(mut event: NoteEvent<()>, channel: u8, note: u8) -> NoteEvent<()> {
+    match &mut event {
+        NoteEvent::NoteOn { note: n, .. }
+        | NoteEvent::NoteOff { note: n, .. }
+        | NoteEvent::Choke { note: n, .. }
+        | NoteEvent::VoiceTerminated { note: n, .. }
+        | NoteEvent::PolyPressure { note: n, .. }
+        | NoteEvent::PolyVolume { note: n, .. }
+        | NoteEvent::PolyPan { note: n, .. }
+        | NoteEvent::PolyTuning { note: n, .. }
+        | NoteEvent::PolyVibrato { note: n, .. }
+        | NoteEvent::PolyExpression { note: n, .. }
+        | NoteEvent::PolyBrightness { note: n, .. } => *n = note,
+        _ => {}
+    }
+
+    with_channel(event, channel)
+}
+
+/// The next event from the `input` channel, `None` listens to every channel (omni). Events from
+/// the other channels are passed through untouched with `pass_other`, dropped otherwise.
+pub fn next_input_event<P: Plugin<SysExMessage = ()>>(
+    input: Option<u8>,
+    pass_other: bool,
+    ctx: &mut impl ProcessContext<P>,
+) -> Option<NoteEvent<()>> {
+    while let Some(event) = ctx.next_event() {
+        match (input, event.channel()) {
+            (Some(input), Some(channel)) if channel != input => {
+                if pass_other {
+                    ctx.send_event(event);
+                }
+            }
+            _ => return Some(event),
+        }
+    }
+
+    None
+}
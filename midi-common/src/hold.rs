@@ -0,0 +1,124 @@
+use crate::NotesState;
+use nih_plug::midi::control_change::DAMPER_PEDAL;
+use nih_plug::prelude::*;
+
+/// What happens to a type of message while notes are held through a switch.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldPolicy {
+    #[name = "Drop"]
+    Drop,
+
+    #[name = "Forward"]
+    Forward,
+
+    /// Only per-note messages of a held note and channel messages of a channel with a held note.
+    #[name = "Held Voices"]
+    HeldVoices,
+}
+
+impl HoldPolicy {
+    /// Whether a message is forwarded, `held` tells if its voice is still held.
+    pub fn forwards(self, held: bool) -> bool {
+        match self {
+            Self::Drop => false,
+            Self::Forward => true,
+            Self::HeldVoices => held,
+        }
+    }
+}
+
+/// The hold policies of a switching plugin, one per type of message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HoldPolicies {
+    pub pitch_bend: HoldPolicy,
+    pub aftertouch: HoldPolicy,
+    pub controllers: HoldPolicy,
+    pub poly: HoldPolicy,
+}
+
+impl HoldPolicies {
+    /// The policy for the event while notes are held, `None` for events handled by the switch
+    /// itself.
+    pub fn policy(&self, event: &NoteEvent<()>) -> Option<HoldPolicy> {
+        match event {
+            NoteEvent::MidiPitchBend { .. } => Some(self.pitch_bend),
+            NoteEvent::MidiChannelPressure { .. } => Some(self.aftertouch),
+            NoteEvent::MidiCC {
+                cc: DAMPER_PEDAL, ..
+            } => None,
+            NoteEvent::MidiCC { .. } => Some(self.controllers),
+            NoteEvent::PolyBrightness { .. }
+            | NoteEvent::PolyExpression { .. }
+            | NoteEvent::PolyPan { .. }
+            | NoteEvent::PolyPressure { .. }
+            | NoteEvent::PolyTuning { .. }
+            | NoteEvent::PolyVibrato { .. }
+            | NoteEvent::PolyVolume { .. } => Some(self.poly),
+            _ => None,
+        }
+    }
+
+    /// Whether the event, sent on `channel`, is forwarded while `notes` are held.
+    pub fn forwards(
+        &self,
+        event: &NoteEvent<()>,
+        channel: u8,
+        notes: &NotesState,
+        mpe: bool,
+    ) -> bool {
+        self.policy(event).is_some_and(|policy| {
+            // MPE voices still held keep their per-note expression while they decay
+            policy.forwards(notes.is_held_on(channel, event))
+                || (mpe && notes.is_held_voice_event(event))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICIES: HoldPolicies = HoldPolicies {
+        pitch_bend: HoldPolicy::Forward,
+        aftertouch: HoldPolicy::Drop,
+        controllers: HoldPolicy::HeldVoices,
+        poly: HoldPolicy::Drop,
+    };
+
+    fn cc(channel: u8, cc: u8) -> NoteEvent<()> {
+        NoteEvent::MidiCC {
+            timing: 0,
+            channel,
+            cc,
+            value: 1.0,
+        }
+    }
+
+    #[test]
+    fn each_type_of_message_takes_its_policy() {
+        let mut notes = NotesState::default();
+        notes.set_on(2, 60);
+
+        let bend = NoteEvent::MidiPitchBend {
+            timing: 0,
+            channel: 5,
+            value: 0.5,
+        };
+        assert!(POLICIES.forwards(&bend, 5, &notes, false));
+
+        assert!(POLICIES.forwards(&cc(2, 1), 2, &notes, false));
+        assert!(!POLICIES.forwards(&cc(3, 1), 3, &notes, false));
+        assert!(!POLICIES.forwards(&cc(2, DAMPER_PEDAL), 2, &notes, false));
+
+        let pressure = |note| NoteEvent::PolyPressure {
+            timing: 0,
+            voice_id: None,
+            channel: 2,
+            note,
+            pressure: 0.5,
+        };
+        assert!(!POLICIES.forwards(&pressure(60), 2, &notes, false));
+        assert!(POLICIES.forwards(&pressure(60), 2, &notes, true));
+        assert!(!POLICIES.forwards(&pressure(61), 2, &notes, true));
+    }
+}
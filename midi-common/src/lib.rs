@@ -0,0 +1,37 @@
+//! Midi helpers shared by the plugins of the suite.
+
+mod cc;
+#[cfg(feature = "editor")]
+pub mod editor;
+mod event;
+mod hold;
+mod monitor;
+mod notes;
+mod overlay;
+mod parameter;
+mod params;
+mod preset;
+mod program;
+mod setup;
+mod velocity;
+
+pub use cc::{
+    ControlChange, ControllerValue, is_lsb_controller, logical_controller, lsb_controller,
+};
+pub use event::{event_note, next_input_event, with_channel, with_note};
+pub use hold::{HoldPolicies, HoldPolicy};
+pub use monitor::{EventMonitor, MonitorEntry, MonitorKind, MonitoredContext, export_monitor};
+pub use notes::{DamperState, NoteMap, NotesState};
+pub use overlay::{StateOverlay, StateView};
+pub use parameter::{
+    MAX_14_BIT, MPE_CONFIGURATION, MPE_MANAGER_CHANNEL, PITCH_BEND_SENSITIVITY, ParameterChange,
+    ParameterKind,
+};
+pub use params::{
+    controller_value, fine_param, input_channel, input_channel_param, mpe_members,
+    nrpn_param_change, transpose_semitones, velocity_param, velocity_transform,
+};
+pub use preset::{Preset, check_presets};
+pub use program::{BankProgram, ProgramChangePolicy, ProgramMap, WithProgramMap};
+pub use setup::{SETUP_FORMAT, Setup, SetupError, SetupField};
+pub use velocity::{VelocityCurve, VelocityTransform};
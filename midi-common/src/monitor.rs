@@ -0,0 +1,332 @@
+use crossbeam::queue::ArrayQueue;
+use nih_plug::prelude::*;
+use std::fmt::Write as _;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What a line of the monitor shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorKind {
+    /// A MIDI 1.0 message received by the plugin.
+    In([u8; 3]),
+
+    /// A MIDI 1.0 message sent by the plugin.
+    Out([u8; 3]),
+
+    /// The plugin moved to the named state.
+    State(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonitorEntry {
+    /// Samples since the plugin started processing.
+    pub sample: u64,
+    pub kind: MonitorKind,
+}
+
+impl MonitorEntry {
+    /// The entry as a line of text, its time in seconds and its channel counted from 1.
+    pub fn line(&self, sample_rate: f32) -> String {
+        let seconds = self.sample as f64 / sample_rate.max(1.0) as f64;
+
+        match self.kind {
+            MonitorKind::In(data) => format!("{seconds:10.4}  in   {}", message(data)),
+            MonitorKind::Out(data) => format!("{seconds:10.4}  out  {}", message(data)),
+            MonitorKind::State(state) => format!("{seconds:10.4}  ---  {state}"),
+        }
+    }
+}
+
+fn message(data: [u8; 3]) -> String {
+    let [status, a, b] = data;
+    let channel = (status & 0x0F) + 1;
+
+    match status & 0xF0 {
+        0x80 => format!("ch{channel} note-off {a} {b}"),
+        0x90 => format!("ch{channel} note-on {a} {b}"),
+        0xA0 => format!("ch{channel} poly-pressure {a} {b}"),
+        0xB0 => format!("ch{channel} cc {a} {b}"),
+        0xC0 => format!("ch{channel} program {a}"),
+        0xD0 => format!("ch{channel} pressure {a}"),
+        0xE0 => format!("ch{channel} pitch-bend {}", ((b as u16) << 7) | a as u16),
+        _ => format!("{status:#04x} {a} {b}"),
+    }
+}
+
+/// The events and state transitions of the last blocks, filled from the audio thread while
+/// recording and drained by the editor. Once full, the oldest entries are overwritten. Recording
+/// never allocates nor blocks.
+pub struct EventMonitor {
+    entries: ArrayQueue<MonitorEntry>,
+    recording: AtomicBool,
+    sample_rate: AtomicF32,
+}
+
+impl Default for EventMonitor {
+    fn default() -> Self {
+        Self::new(4096)
+    }
+}
+
+impl EventMonitor {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: ArrayQueue::new(capacity.max(1)),
+            recording: AtomicBool::new(false),
+            sample_rate: AtomicF32::new(44100.0),
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    /// Set from `initialize`, the editor shows the time of the entries in seconds.
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    pub fn set_recording(&self, recording: bool) {
+        self.recording.store(recording, Ordering::Relaxed);
+    }
+
+    pub fn push(&self, entry: MonitorEntry) {
+        if self.is_recording() {
+            self.entries.force_push(entry);
+        }
+    }
+
+    /// Logs the MIDI 1.0 messages of `event`, events without a MIDI equivalent are left out.
+    pub fn event(&self, sample: u64, event: &NoteEvent<()>, output: bool) {
+        if !self.is_recording() {
+            return;
+        }
+
+        let Some(data) = midi1(event) else {
+            return;
+        };
+
+        let kind = if output {
+            MonitorKind::Out(data)
+        } else {
+            MonitorKind::In(data)
+        };
+
+        self.push(MonitorEntry { sample, kind });
+    }
+
+    pub fn state(&self, sample: u64, state: &'static str) {
+        self.push(MonitorEntry {
+            sample,
+            kind: MonitorKind::State(state),
+        });
+    }
+
+    /// Takes the entries logged since the last call, oldest first.
+    pub fn drain(&self) -> impl Iterator<Item = MonitorEntry> + '_ {
+        std::iter::from_fn(|| self.entries.pop())
+    }
+}
+
+/// The MIDI 1.0 message of a channel voice event, the values rounded to 7 bits (14 for the pitch
+/// bend).
+fn midi1(event: &NoteEvent<()>) -> Option<[u8; 3]> {
+    let seven = |value: f32| (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+
+    let data = match *event {
+        NoteEvent::NoteOn {
+            channel,
+            note,
+            velocity,
+            ..
+        } => {
+            // a zero velocity would turn the note on into a note off
+            [0x90 | channel, note, seven(velocity).max(1)]
+        }
+        NoteEvent::NoteOff {
+            channel,
+            note,
+            velocity,
+            ..
+        } => [0x80 | channel, note, seven(velocity)],
+        NoteEvent::PolyPressure {
+            channel,
+            note,
+            pressure,
+            ..
+        } => [0xA0 | channel, note, seven(pressure)],
+        NoteEvent::MidiChannelPressure {
+            channel, pressure, ..
+        } => [0xD0 | channel, seven(pressure), 0],
+        NoteEvent::MidiPitchBend { channel, value, .. } => {
+            let value = (value.clamp(0.0, 1.0) * 16383.0).round() as u16;
+            [0xE0 | channel, (value & 0x7F) as u8, (value >> 7) as u8]
+        }
+        NoteEvent::MidiCC {
+            channel, cc, value, ..
+        } => [0xB0 | channel, cc, seven(value)],
+        NoteEvent::MidiProgramChange {
+            channel, program, ..
+        } => [0xC0 | channel, program, 0],
+        _ => return None,
+    };
+
+    Some(data)
+}
+
+/// Writes `entries` to a text file, one line each.
+pub fn export_monitor(entries: &[MonitorEntry], sample_rate: f32, path: &Path) -> io::Result<()> {
+    let mut text = String::new();
+
+    for entry in entries {
+        let _ = writeln!(text, "{}", entry.line(sample_rate));
+    }
+
+    std::fs::write(path, text)
+}
+
+/// Forwards to the context of a block of the plugin `P`, logging the events read and sent on the
+/// way.
+pub struct MonitoredContext<'a, P, C> {
+    inner: &'a mut C,
+    monitor: &'a EventMonitor,
+    /// The sample the block starts on.
+    position: u64,
+    plugin: PhantomData<fn() -> P>,
+}
+
+impl<'a, P, C> MonitoredContext<'a, P, C> {
+    pub fn new(inner: &'a mut C, monitor: &'a EventMonitor, position: u64) -> Self {
+        Self {
+            inner,
+            monitor,
+            position,
+            plugin: PhantomData,
+        }
+    }
+}
+
+impl<P, C> ProcessContext<P> for MonitoredContext<'_, P, C>
+where
+    P: Plugin<SysExMessage = ()>,
+    C: ProcessContext<P>,
+{
+    fn plugin_api(&self) -> PluginApi {
+        self.inner.plugin_api()
+    }
+
+    fn execute_background(&self, task: P::BackgroundTask) {
+        self.inner.execute_background(task);
+    }
+
+    fn execute_gui(&self, task: P::BackgroundTask) {
+        self.inner.execute_gui(task);
+    }
+
+    fn transport(&self) -> &Transport {
+        self.inner.transport()
+    }
+
+    fn next_event(&mut self) -> Option<PluginNoteEvent<P>> {
+        let event = self.inner.next_event()?;
+
+        self.monitor
+            .event(self.position + event.timing() as u64, &event, false);
+        Some(event)
+    }
+
+    fn send_event(&mut self, event: PluginNoteEvent<P>) {
+        self.monitor
+            .event(self.position + event.timing() as u64, &event, true);
+        self.inner.send_event(event);
+    }
+
+    fn set_latency_samples(&self, samples: u32) {
+        self.inner.set_latency_samples(samples);
+    }
+
+    fn set_current_voice_capacity(&self, capacity: u32) {
+        self.inner.set_current_voice_capacity(capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_only_while_recording() {
+        let monitor = EventMonitor::new(2);
+        let on = NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 1,
+            note: 60,
+            velocity: 100.0 / 127.0,
+        };
+
+        monitor.event(0, &on, false);
+        assert_eq!(monitor.drain().count(), 0);
+
+        monitor.set_recording(true);
+        monitor.event(10, &on, false);
+        monitor.state(20, "On");
+        monitor.event(30, &on, true);
+
+        // the oldest entry made room for the last one
+        let entries: Vec<_> = monitor.drain().collect();
+        assert_eq!(
+            entries,
+            [
+                MonitorEntry {
+                    sample: 20,
+                    kind: MonitorKind::State("On"),
+                },
+                MonitorEntry {
+                    sample: 30,
+                    kind: MonitorKind::Out([0x91, 60, 100]),
+                },
+            ]
+        );
+        assert_eq!(entries[1].line(10.0), "    3.0000  out  ch2 note-on 60 100");
+    }
+
+    #[test]
+    fn events_read_as_midi1() {
+        for value in 0..128u8 {
+            let unit = value as f32 / 127.0;
+            let cc = NoteEvent::MidiCC {
+                timing: 0,
+                channel: 3,
+                cc: 74,
+                value: unit,
+            };
+
+            assert_eq!(midi1(&cc), Some([0xB3, 74, value]));
+        }
+
+        let silent = NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 15,
+            note: 127,
+            velocity: 0.0,
+        };
+        assert_eq!(midi1(&silent), Some([0x9F, 127, 1]));
+
+        let bend = |value| NoteEvent::MidiPitchBend {
+            timing: 0,
+            channel: 0,
+            value,
+        };
+        assert_eq!(midi1(&bend(0.0)), Some([0xE0, 0, 0]));
+        assert_eq!(midi1(&bend(0.5)), Some([0xE0, 0, 0x40]));
+        assert_eq!(midi1(&bend(1.0)), Some([0xE0, 0x7F, 0x7F]));
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{event_note, with_note};
+use nih_plug::{midi::control_change::SOUND_CONTROLLER_5, prelude::*};
+
+/// The notes held on each of the 16 channels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NotesState([u128; 16]);
+
+impl NotesState {
+    pub fn is_all_off(&self) -> bool {
+        self.0.iter().all(|notes| *notes == 0)
+    }
+
+    pub fn is_channel_off(&self, channel: u8) -> bool {
+        self.0[channel as usize & 0x0F] == 0
+    }
+
+    pub fn is_on(&self, channel: u8, note: u8) -> bool {
+        self.0[channel as usize & 0x0F] & note_mask(note) != 0
+    }
+
+    pub fn set_off(&mut self, channel: u8, note: u8) {
+        self.0[channel as usize & 0x0F] &= !note_mask(note);
+    }
+
+    pub fn set_on(&mut self, channel: u8, note: u8) {
+        self.0[channel as usize & 0x0F] |= note_mask(note);
+    }
+
+    /// Removes the notes held on `channel`, returning them.
+    pub fn take_channel(&mut self, channel: u8) -> NotesState {
+        let mut taken = NotesState::default();
+        let channel = channel as usize & 0x0F;
+
+        taken.0[channel] = std::mem::take(&mut self.0[channel]);
+        taken
+    }
+
+    /// The held notes as `(channel, note)`, by channel then note.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.0.iter().enumerate().flat_map(|(channel, notes)| {
+            let mut notes = *notes;
+
+            std::iter::from_fn(move || {
+                if notes == 0 {
+                    return None;
+                }
+
+                let note = notes.trailing_zeros() as u8;
+                notes &= notes - 1;
+                Some((channel as u8, note))
+            })
+        })
+    }
+
+    /// Whether the event targets a held voice on `channel`: the note of a per-note event is held,
+    /// or any note is held for a channel event.
+    pub fn is_held_on(&self, channel: u8, event: &NoteEvent<()>) -> bool {
+        match event_note(event) {
+            Some(note) => self.is_on(channel, note),
+            None => !self.is_channel_off(channel),
+        }
+    }
+
+    /// Whether the event is expression of a voice still held on its MPE member channel: per-note
+    /// events of a held note, or pitch bend, pressure and timbre of a channel with a held note.
+    pub fn is_held_voice_event(&self, event: &NoteEvent<()>) -> bool {
+        match *event {
+            NoteEvent::PolyBrightness { channel, note, .. }
+            | NoteEvent::PolyExpression { channel, note, .. }
+            | NoteEvent::PolyPan { channel, note, .. }
+            | NoteEvent::PolyPressure { channel, note, .. }
+            | NoteEvent::PolyTuning { channel, note, .. }
+            | NoteEvent::PolyVibrato { channel, note, .. }
+            | NoteEvent::PolyVolume { channel, note, .. } => self.is_on(channel, note),
+            NoteEvent::MidiChannelPressure { channel, .. }
+            | NoteEvent::MidiPitchBend { channel, .. }
+            | NoteEvent::MidiCC {
+                channel,
+                cc: SOUND_CONTROLLER_5,
+                ..
+            } => !self.is_channel_off(channel),
+            _ => false,
+        }
+    }
+
+    /// Sends a note off for every held note on the channel it is held on.
+    pub fn send_all_note_off<P: Plugin<SysExMessage = ()>>(
+        &self,
+        ctx: &mut impl ProcessContext<P>,
+    ) {
+        for (channel, note) in self.iter() {
+            ctx.send_event(NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel,
+                note,
+                velocity: 0.0,
+            });
+        }
+    }
+}
+
+/// The output channel and note of each held input note, so the events of a note still reach it
+/// after the transpose or the patch channel changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteMap([[Option<(u8, u8)>; 128]; 16]);
+
+impl Default for NoteMap {
+    fn default() -> Self {
+        Self([[None; 128]; 16])
+    }
+}
+
+impl NoteMap {
+    /// Moves a per-note event to its output channel and note: where the note was pressed while it
+    /// is still held in `notes`, `map(channel, note)` for a new note. `None` drops the event, the
+    /// note off of a note that is not held is dropped since it was cut or never sent.
+    pub fn route(
+        &mut self,
+        event: NoteEvent<()>,
+        notes: &NotesState,
+        map: impl FnOnce(u8, u8) -> Option<(u8, u8)>,
+    ) -> Option<NoteEvent<()>> {
+        let (Some(channel), Some(note)) = (event.channel(), event_note(&event)) else {
+            return Some(event);
+        };
+
+        let slot = &mut self.0[channel as usize & 0x0F][note as usize & 0x7F];
+
+        let held = match event {
+            NoteEvent::NoteOn { .. } => None,
+            _ => slot.filter(|&(channel, note)| notes.is_on(channel, note)),
+        };
+
+        let output = match event {
+            NoteEvent::NoteOff { .. } | NoteEvent::Choke { .. } => held,
+            _ => held.or_else(|| map(channel, note)),
+        };
+
+        match event {
+            NoteEvent::NoteOn { .. } => *slot = output,
+            NoteEvent::NoteOff { .. } | NoteEvent::Choke { .. } => *slot = None,
+            _ => {}
+        }
+
+        output.map(|(channel, note)| with_note(event, channel, note))
+    }
+}
+
+/// Whether the damper (sustain) pedal is down.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DamperState(bool);
+
+impl DamperState {
+    pub fn is_off(&self) -> bool {
+        !self.0
+    }
+
+    pub fn set_off(&mut self) {
+        self.0 = false;
+    }
+
+    pub fn set_on(&mut self) {
+        self.0 = true;
+    }
+}
+
+#[inline]
+fn note_mask(note: u8) -> u128 {
+    1 << (note & 0x7F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iter_lists_notes_per_channel() {
+        let mut notes = NotesState::default();
+
+        notes.set_on(3, 127);
+        notes.set_on(0, 60);
+        notes.set_on(3, 0);
+
+        assert_eq!(
+            notes.iter().collect::<Vec<_>>(),
+            [(0, 60), (3, 0), (3, 127)]
+        );
+
+        notes.set_off(3, 127);
+        notes.set_off(3, 0);
+
+        assert!(notes.is_channel_off(3));
+        assert!(!notes.is_all_off());
+        assert!(notes.is_on(0, 60));
+    }
+
+    #[test]
+    fn held_notes_keep_their_output() {
+        let mut map = NoteMap::default();
+        let mut notes = NotesState::default();
+
+        let on = NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        };
+        let off = NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 0.0,
+        };
+
+        let pressed = map.route(on, &notes, |_, note| Some((2, note + 12)));
+        assert_eq!(pressed, Some(with_note(on, 2, 72)));
+        notes.set_on(2, 72);
+
+        // the transpose changed while the key is held
+        let released = map.route(off, &notes, |_, note| Some((3, note - 12)));
+        assert_eq!(released, Some(with_note(off, 2, 72)));
+        notes.set_off(2, 72);
+
+        let dropped = map.route(on, &notes, |_, _| None);
+        assert_eq!(dropped, None);
+
+        let unknown = map.route(off, &notes, |_, note| Some((3, note)));
+        assert_eq!(unknown, None);
+    }
+
+    #[test]
+    fn take_channel_leaves_the_other_channels() {
+        let mut notes = NotesState::default();
+
+        notes.set_on(1, 60);
+        notes.set_on(4, 60);
+
+        let taken = notes.take_channel(1);
+
+        assert!(taken.is_on(1, 60));
+        assert!(!taken.is_on(4, 60));
+        assert!(notes.is_channel_off(1));
+        assert!(notes.is_on(4, 60));
+    }
+}
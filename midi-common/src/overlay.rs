@@ -0,0 +1,78 @@
+use crate::{DamperState, NotesState};
+use crossbeam::queue::ArrayQueue;
+
+/// The state of a switching plugin at the end of a block, for the editor.
+#[derive(Debug, Clone, Copy)]
+pub struct StateView<S> {
+    /// The name of the `InternalState` variant.
+    pub state: &'static str,
+    pub notes: NotesState,
+    /// The notes among `notes` held from before the last switch.
+    pub switched: NotesState,
+    pub damper: DamperState,
+    /// The parameters sent last, `None` while inactive.
+    pub snapshot: Option<S>,
+}
+
+impl<S> StateView<S> {
+    pub fn off() -> Self {
+        Self {
+            state: "Off",
+            notes: NotesState::default(),
+            switched: NotesState::default(),
+            damper: DamperState::default(),
+            snapshot: None,
+        }
+    }
+}
+
+/// Hands the last [`StateView`] published by the audio thread to the editor. The slot is
+/// overwritten by each block and emptied by the reader, publishing never allocates nor blocks.
+pub struct StateOverlay<S> {
+    latest: ArrayQueue<StateView<S>>,
+}
+
+impl<S> Default for StateOverlay<S> {
+    fn default() -> Self {
+        Self {
+            latest: ArrayQueue::new(1),
+        }
+    }
+}
+
+impl<S> StateOverlay<S> {
+    pub fn publish(&self, view: StateView<S>) {
+        self.latest.force_push(view);
+    }
+
+    /// The view published last, `None` when none was published since the previous call.
+    pub fn take(&self) -> Option<StateView<S>> {
+        self.latest.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_returns_the_last_view_once() {
+        let overlay = StateOverlay::<u8>::default();
+
+        assert!(overlay.take().is_none());
+
+        overlay.publish(StateView {
+            snapshot: Some(1),
+            ..StateView::off()
+        });
+        overlay.publish(StateView {
+            state: "On",
+            snapshot: Some(2),
+            ..StateView::off()
+        });
+
+        let view = overlay.take().unwrap();
+        assert_eq!((view.state, view.snapshot), ("On", Some(2)));
+        assert!(overlay.take().is_none());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::ControlChange;
+use nih_plug::midi::control_change::{
+    DATA_ENTRY_LSB, DATA_ENTRY_MSB, NON_REGISTERED_PARAMETER_NUMBER_LSB,
+    NON_REGISTERED_PARAMETER_NUMBER_MSB, REGISTERED_PARAMETER_NUMBER_LSB,
+    REGISTERED_PARAMETER_NUMBER_MSB,
+};
+use serde::{Deserialize, Serialize};
+
+/// Highest value of a 14-bit parameter number or data value.
+pub const MAX_14_BIT: u16 = 0x3FFF;
+
+/// RPN 0,0: pitch bend sensitivity, data MSB in semitones and LSB in cents.
+pub const PITCH_BEND_SENSITIVITY: u16 = 0;
+
+/// RPN 0,6: MPE configuration message, data MSB is the number of member channels.
+pub const MPE_CONFIGURATION: u16 = 6;
+
+/// Manager channel of the MPE lower zone, its member channels follow it.
+pub const MPE_MANAGER_CHANNEL: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParameterKind {
+    /// RPN, addressed with CC 101 / 100.
+    Registered,
+
+    /// NRPN, addressed with CC 99 / 98.
+    NonRegistered,
+}
+
+impl ParameterKind {
+    fn address_ccs(self) -> (u8, u8) {
+        match self {
+            Self::Registered => (
+                REGISTERED_PARAMETER_NUMBER_MSB,
+                REGISTERED_PARAMETER_NUMBER_LSB,
+            ),
+            Self::NonRegistered => (
+                NON_REGISTERED_PARAMETER_NUMBER_MSB,
+                NON_REGISTERED_PARAMETER_NUMBER_LSB,
+            ),
+        }
+    }
+}
+
+/// A RPN / NRPN write: the parameter address followed by the data entry MSB / LSB and,
+/// when `terminate` is set, the null RPN (127 / 127) so later data entry messages
+/// are not applied to the same parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParameterChange {
+    pub kind: ParameterKind,
+    pub number: u16,
+    pub value: u16,
+    pub terminate: bool,
+}
+
+impl ParameterChange {
+    pub fn rpn(number: u16, value: u16) -> Self {
+        Self::new(ParameterKind::Registered, number, value)
+    }
+
+    pub fn nrpn(number: u16, value: u16) -> Self {
+        Self::new(ParameterKind::NonRegistered, number, value)
+    }
+
+    /// The MPE configuration message, sent on the manager channel of the zone.
+    pub fn mpe_configuration(member_channels: u8) -> Self {
+        Self::rpn(MPE_CONFIGURATION, (member_channels.min(15) as u16) << 7)
+    }
+
+    /// The pitch bend range, `cents` are clamped to 99.
+    pub fn pitch_bend_range(semitones: u8, cents: u8) -> Self {
+        let value = (((semitones & 0x7F) as u16) << 7) | cents.min(99) as u16;
+        Self::rpn(PITCH_BEND_SENSITIVITY, value)
+    }
+
+    fn new(kind: ParameterKind, number: u16, value: u16) -> Self {
+        Self {
+            kind,
+            number: number.min(MAX_14_BIT),
+            value: value.min(MAX_14_BIT),
+            terminate: false,
+        }
+    }
+
+    pub fn terminated(mut self) -> Self {
+        self.terminate = true;
+        self
+    }
+
+    /// The control change sequence for this write, in sending order.
+    pub fn messages(&self, channel: u8) -> impl Iterator<Item = ControlChange> {
+        let (msb, lsb) = self.kind.address_ccs();
+
+        let write = [
+            ControlChange::new(channel, msb, (self.number >> 7) as u8),
+            ControlChange::new(channel, lsb, self.number as u8),
+            ControlChange::new(channel, DATA_ENTRY_MSB, (self.value >> 7) as u8),
+            ControlChange::new(channel, DATA_ENTRY_LSB, self.value as u8),
+        ];
+
+        let null = self.terminate.then(|| {
+            [
+                ControlChange::new(channel, REGISTERED_PARAMETER_NUMBER_MSB, 0x7F),
+                ControlChange::new(channel, REGISTERED_PARAMETER_NUMBER_LSB, 0x7F),
+            ]
+        });
+
+        write.into_iter().chain(null.into_iter().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(change: ParameterChange, channel: u8) -> Vec<[u8; 3]> {
+        change
+            .messages(channel)
+            .map(ControlChange::to_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn rpn_pitch_bend_range() {
+        assert_eq!(
+            bytes(ParameterChange::rpn(0, 12 << 7), 0),
+            [[0xB0, 101, 0], [0xB0, 100, 0], [0xB0, 6, 12], [0xB0, 38, 0]]
+        );
+    }
+
+    #[test]
+    fn pitch_bend_range_in_semitones_and_cents() {
+        assert_eq!(
+            bytes(ParameterChange::pitch_bend_range(2, 50).terminated(), 1),
+            [
+                [0xB1, 101, 0],
+                [0xB1, 100, 0],
+                [0xB1, 6, 2],
+                [0xB1, 38, 50],
+                [0xB1, 101, 127],
+                [0xB1, 100, 127]
+            ]
+        );
+    }
+
+    #[test]
+    fn nrpn_splits_number_and_value() {
+        assert_eq!(
+            bytes(ParameterChange::nrpn(0x0123, 0x3FFF), 2),
+            [
+                [0xB2, 99, 0x02],
+                [0xB2, 98, 0x23],
+                [0xB2, 6, 0x7F],
+                [0xB2, 38, 0x7F]
+            ]
+        );
+    }
+
+    #[test]
+    fn terminated_ends_with_null_rpn() {
+        assert_eq!(
+            bytes(ParameterChange::nrpn(5, 1).terminated(), 15),
+            [
+                [0xBF, 99, 0],
+                [0xBF, 98, 5],
+                [0xBF, 6, 0],
+                [0xBF, 38, 1],
+                [0xBF, 101, 127],
+                [0xBF, 100, 127]
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_is_clamped() {
+        let change = ParameterChange::nrpn(u16::MAX, u16::MAX);
+
+        assert_eq!(change.number, MAX_14_BIT);
+        assert_eq!(change.value, MAX_14_BIT);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{ControllerValue, MAX_14_BIT, ParameterChange, VelocityCurve, VelocityTransform};
+use nih_plug::prelude::*;
+use std::sync::Arc;
+
+/// A 14-bit slot overriding its 7-bit counterpart, `-1` keeps the 7-bit value.
+pub fn fine_param(name: &str) -> IntParam {
+    IntParam::new(
+        name,
+        -1,
+        IntRange::Linear {
+            min: -1,
+            max: MAX_14_BIT as i32,
+        },
+    )
+}
+
+/// The value of a controller slot: the 14-bit `fine` slot of [`fine_param`] when set, else the
+/// 7-bit `coarse` one, `None` when it is `-1` too.
+pub fn controller_value(coarse: &IntParam, fine: &IntParam) -> Option<ControllerValue> {
+    match (coarse.value(), fine.value()) {
+        (coarse, -1) if coarse < 0 => None,
+        (coarse, -1) => Some(ControllerValue::Coarse(coarse.min(127) as u8)),
+        (_, fine) => Some(ControllerValue::Fine(
+            fine.clamp(0, MAX_14_BIT as i32) as u16
+        )),
+    }
+}
+
+/// The channel the plugin listens to, `0` listens to every channel, see [`input_channel`].
+pub fn input_channel_param() -> IntParam {
+    IntParam::new("Input Channel", 0, IntRange::Linear { min: 0, max: 16 })
+        .with_value_to_string(Arc::new(|value| match value {
+            0 => String::from("Omni"),
+            channel => channel.to_string(),
+        }))
+        .with_string_to_value(Arc::new(|string| match string.trim() {
+            omni if omni.eq_ignore_ascii_case("omni") => Some(0),
+            channel => channel.parse().ok(),
+        }))
+}
+
+/// The input channel set by an [`input_channel_param`], `None` in omni mode.
+pub fn input_channel(param: &IntParam) -> Option<u8> {
+    match param.value() {
+        0 => None,
+        channel => Some(channel.clamp(1, 16) as u8 - 1),
+    }
+}
+
+/// A MIDI velocity of the velocity mapping.
+pub fn velocity_param(name: &str, default: i32) -> IntParam {
+    IntParam::new(name, default, IntRange::Linear { min: 1, max: 127 })
+}
+
+/// The transpose in semitones of a `transpose` and an `octave` shift.
+pub fn transpose_semitones(transpose: &IntParam, octave: &IntParam) -> i8 {
+    (transpose.value() + octave.value() * 12).clamp(-127, 127) as i8
+}
+
+/// The velocity mapping set by a curve and the [`velocity_param`]s of its fixed value and clamp.
+pub fn velocity_transform(
+    curve: &EnumParam<VelocityCurve>,
+    fixed: &IntParam,
+    min: &IntParam,
+    max: &IntParam,
+) -> VelocityTransform {
+    let velocity = |param: &IntParam| param.value().clamp(1, 127) as u8;
+
+    VelocityTransform {
+        curve: curve.value(),
+        fixed: velocity(fixed),
+        min: velocity(min),
+        max: velocity(max),
+    }
+}
+
+/// The member channel count of the MPE lower zone, `None` outside of MPE mode.
+pub fn mpe_members(enabled: &BoolParam, members: &IntParam) -> Option<u8> {
+    enabled.value().then(|| members.value().clamp(1, 15) as u8)
+}
+
+/// The NRPN of a slot, `None` while its `number` is `-1`.
+pub fn nrpn_param_change(number: &IntParam, value: &IntParam) -> Option<ParameterChange> {
+    let number = u16::try_from(number.value()).ok()?;
+    let value = value.value().clamp(0, MAX_14_BIT as i32) as u16;
+
+    Some(ParameterChange::nrpn(number, value).terminated())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i32) -> IntParam {
+        IntParam::new(
+            "Test",
+            value,
+            IntRange::Linear {
+                min: -1,
+                max: 20_000,
+            },
+        )
+    }
+
+    #[test]
+    fn fine_slot_overrides_coarse() {
+        assert_eq!(controller_value(&int(-1), &int(-1)), None);
+        assert_eq!(
+            controller_value(&int(200), &int(-1)),
+            Some(ControllerValue::Coarse(127))
+        );
+        assert_eq!(
+            controller_value(&int(-1), &int(8192)),
+            Some(ControllerValue::Fine(8192))
+        );
+        assert_eq!(
+            controller_value(&int(100), &int(20_000)),
+            Some(ControllerValue::Fine(MAX_14_BIT))
+        );
+    }
+
+    #[test]
+    fn octaves_add_to_the_transpose() {
+        assert_eq!(transpose_semitones(&int(-5), &int(2)), 19);
+        assert_eq!(transpose_semitones(&int(100), &int(4)), 127);
+    }
+
+    #[test]
+    fn velocity_transform_clamps_to_midi_velocities() {
+        let transform = velocity_transform(
+            &EnumParam::new("Curve", VelocityCurve::Soft),
+            &int(200),
+            &int(-1),
+            &int(64),
+        );
+
+        assert_eq!(transform.curve, VelocityCurve::Soft);
+        assert_eq!(
+            (transform.fixed, transform.min, transform.max),
+            (127, 1, 64)
+        );
+    }
+
+    #[test]
+    fn mpe_members_only_in_mpe_mode() {
+        assert_eq!(mpe_members(&BoolParam::new("MPE", false), &int(5)), None);
+        assert_eq!(mpe_members(&BoolParam::new("MPE", true), &int(5)), Some(5));
+        assert_eq!(mpe_members(&BoolParam::new("MPE", true), &int(-1)), Some(1));
+    }
+
+    #[test]
+    fn nrpn_slot_disabled_and_clamped() {
+        assert_eq!(nrpn_param_change(&int(-1), &int(5)), None);
+        assert_eq!(
+            nrpn_param_change(&int(300), &int(20_000)),
+            Some(ParameterChange::nrpn(300, MAX_14_BIT).terminated())
+        );
+        assert_eq!(
+            nrpn_param_change(&int(20_000), &int(-1)),
+            Some(ParameterChange::nrpn(MAX_14_BIT, 0).terminated())
+        );
+    }
+}
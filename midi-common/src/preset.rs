@@ -0,0 +1,193 @@
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::{ParamValue, PluginState};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A factory preset: parameter values by parameter id, like a [`Setup`](crate::Setup), written as
+/// the parameter shows them (`"On"`, `"Forward"`, `"10"`), JSON booleans and numbers are accepted
+/// as well. The parameters left out are reset to their default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, Value>,
+}
+
+impl Preset {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|error| format!("invalid preset: {error}"))
+    }
+
+    /// The normalized value of every parameter of `params` by id once the preset is loaded, or
+    /// why it cannot be: an unknown parameter or a value the parameter does not accept.
+    pub fn values(&self, params: &dyn Params) -> Result<Vec<(String, ParamPtr, f32)>, String> {
+        let map = params.param_map();
+
+        if let Some(unknown) = self
+            .params
+            .keys()
+            .find(|id| !map.iter().any(|(param_id, _, _)| param_id == *id))
+        {
+            return Err(format!("{}: unknown parameter \"{unknown}\"", self.name));
+        }
+
+        map.into_iter()
+            .map(|(id, ptr, _)| {
+                let normalized = match self.params.get(&id) {
+                    Some(value) => {
+                        let text = value_text(value);
+
+                        unsafe { ptr.string_to_normalized_value(&text) }.ok_or_else(|| {
+                            format!("{}: invalid value \"{text}\" for \"{id}\"", self.name)
+                        })?
+                    }
+                    None => unsafe { ptr.default_normalized_value() },
+                };
+
+                Ok((id, ptr, normalized))
+            })
+            .collect()
+    }
+
+    /// `state` with the values of the preset, given to the GUI context the way a setup is
+    /// imported so the host is told of every parameter change.
+    pub fn state(
+        &self,
+        params: &dyn Params,
+        mut state: PluginState,
+    ) -> Result<PluginState, String> {
+        for (id, ptr, normalized) in self.values(params)? {
+            let plain = unsafe { ptr.preview_plain(normalized) };
+            let value = match ptr {
+                ParamPtr::FloatParam(_) => ParamValue::F32(plain),
+                ParamPtr::IntParam(_) | ParamPtr::EnumParam(_) => {
+                    ParamValue::I32(plain.round() as i32)
+                }
+                ParamPtr::BoolParam(_) => ParamValue::Bool(plain >= 0.5),
+            };
+
+            state.params.insert(id, value);
+        }
+
+        Ok(state)
+    }
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Bool(true) => "On".to_owned(),
+        Value::Bool(false) => "Off".to_owned(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Parses the factory presets of a plugin and checks them against its parameters, for the tests
+/// of the plugin crates.
+pub fn check_presets(presets: &[&str], params: &dyn Params) -> Result<Vec<Preset>, String> {
+    let presets = presets
+        .iter()
+        .map(|json| Preset::parse(json))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (index, preset) in presets.iter().enumerate() {
+        preset.values(params)?;
+
+        if presets[..index]
+            .iter()
+            .any(|other| other.name == preset.name)
+        {
+            return Err(format!("{}: duplicate preset name", preset.name));
+        }
+    }
+
+    Ok(presets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Params)]
+    struct TestParams {
+        #[id = "on"]
+        on: BoolParam,
+        #[id = "level"]
+        level: IntParam,
+    }
+
+    impl Default for TestParams {
+        fn default() -> Self {
+            Self {
+                on: BoolParam::new("On", false),
+                level: IntParam::new("Level", 5, IntRange::Linear { min: 0, max: 10 }),
+            }
+        }
+    }
+
+    #[test]
+    fn left_out_parameters_take_their_default() {
+        let params = TestParams::default();
+        let preset = Preset::parse(r#"{ "name": "Test", "params": { "on": true } }"#).unwrap();
+
+        let values: Vec<f32> = preset
+            .values(&params)
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, value)| value)
+            .collect();
+
+        assert_eq!(values, [1.0, 0.5]);
+    }
+
+    #[test]
+    fn presets_load_as_a_state() {
+        let params = TestParams::default();
+        let preset = Preset::parse(r#"{ "name": "Test", "params": { "level": 8 } }"#).unwrap();
+        let current = PluginState {
+            version: "1.0.0".to_owned(),
+            params: BTreeMap::from([("on".to_owned(), ParamValue::Bool(true))]),
+            fields: BTreeMap::from([("editor-state".to_owned(), "{}".to_owned())]),
+        };
+
+        let state = preset.state(&params, current).unwrap();
+
+        assert_eq!(
+            state.params,
+            BTreeMap::from([
+                ("level".to_owned(), ParamValue::I32(8)),
+                ("on".to_owned(), ParamValue::Bool(false)),
+            ])
+        );
+        assert_eq!(state.fields.len(), 1);
+    }
+
+    #[test]
+    fn invalid_presets_are_reported() {
+        let params = TestParams::default();
+
+        let unknown = r#"{ "name": "Unknown", "params": { "volume": 3 } }"#;
+        assert_eq!(
+            check_presets(&[unknown], &params).unwrap_err(),
+            "Unknown: unknown parameter \"volume\""
+        );
+
+        // the display name is not the id
+        let by_name = r#"{ "name": "By Name", "params": { "Level": 3 } }"#;
+        assert_eq!(
+            check_presets(&[by_name], &params).unwrap_err(),
+            "By Name: unknown parameter \"Level\""
+        );
+
+        let invalid = r#"{ "name": "Invalid", "params": { "level": "loud" } }"#;
+        assert_eq!(
+            check_presets(&[invalid], &params).unwrap_err(),
+            "Invalid: invalid value \"loud\" for \"level\""
+        );
+
+        let valid = r#"{ "name": "Valid" }"#;
+        assert!(check_presets(&[valid, valid], &params).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::ControlChange;
+use nih_plug::midi::control_change::{BANK_SELECT_LSB, BANK_SELECT_MSB};
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::RwLock;
+
+/// What a switching plugin does with the program changes it receives.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramChangePolicy {
+    #[name = "Drop"]
+    Drop,
+
+    #[name = "Forward"]
+    Forward,
+
+    /// Through the program map, programs without an entry are forwarded unchanged.
+    #[name = "Translate"]
+    Translate,
+
+    /// Selects the snapshot stored at that number, a setlist step of Program Change. Left to the
+    /// plugin, [`Self::send`] drops it.
+    #[name = "Select Step"]
+    SelectStep,
+}
+
+impl ProgramChangePolicy {
+    /// Sends what the incoming `program` becomes on `channel`. A translated program is dropped
+    /// while the map is being edited.
+    pub fn send<P: Plugin<SysExMessage = ()>>(
+        self,
+        timing: u32,
+        channel: u8,
+        program: u8,
+        map: &RwLock<ProgramMap>,
+        ctx: &mut impl ProcessContext<P>,
+    ) {
+        let forward = NoteEvent::MidiProgramChange {
+            timing,
+            channel,
+            program,
+        };
+
+        match self {
+            Self::Drop | Self::SelectStep => {}
+            Self::Forward => ctx.send_event(forward),
+            Self::Translate => match map.try_read().map(|map| map.get(program)) {
+                Ok(Some(target)) => {
+                    for event in target.events(timing, channel) {
+                        ctx.send_event(event);
+                    }
+                }
+                Ok(None) => ctx.send_event(forward),
+                Err(_) => {}
+            },
+        }
+    }
+}
+
+/// A bank and program of the target synth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BankProgram {
+    pub msb: u8,
+    pub lsb: u8,
+    pub program: u8,
+}
+
+impl BankProgram {
+    /// Bank select MSB / LSB followed by the program change, the order receivers expect.
+    pub fn events(self, timing: u32, channel: u8) -> [NoteEvent<()>; 3] {
+        [
+            ControlChange::new(channel, BANK_SELECT_MSB, self.msb).to_event(timing),
+            ControlChange::new(channel, BANK_SELECT_LSB, self.lsb).to_event(timing),
+            NoteEvent::MidiProgramChange {
+                timing,
+                channel,
+                program: self.program & 0x7F,
+            },
+        ]
+    }
+}
+
+impl fmt::Display for BankProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bank {} / {} program {}",
+            self.msb, self.lsb, self.program
+        )
+    }
+}
+
+/// Incoming program numbers mapped to a bank and program of the target synth.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ProgramMap(BTreeMap<u8, BankProgram>);
+
+impl ProgramMap {
+    pub fn get(&self, program: u8) -> Option<BankProgram> {
+        self.0.get(&program).copied()
+    }
+
+    pub fn insert(&mut self, program: u8, target: BankProgram) {
+        self.0.insert(program & 0x7F, target);
+    }
+
+    pub fn remove(&mut self, program: u8) {
+        self.0.remove(&program);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u8, BankProgram)> + '_ {
+        self.0.iter().map(|(program, target)| (*program, *target))
+    }
+
+    /// Parses an entry as typed in the editor: the incoming program then the bank MSB, LSB and
+    /// program it becomes, such as `5 0 1 12`. The target is `None` when only the incoming
+    /// program is given.
+    pub fn parse_entry(text: &str) -> Result<(u8, Option<BankProgram>), String> {
+        let numbers = text
+            .split_whitespace()
+            .map(|number| match number.parse::<u8>() {
+                Ok(number) if number < 128 => Ok(number),
+                _ => Err(format!("\"{number}\" is not a number from 0 to 127")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match numbers[..] {
+            [program] => Ok((program, None)),
+            [program, msb, lsb, target] => Ok((
+                program,
+                Some(BankProgram {
+                    msb,
+                    lsb,
+                    program: target,
+                }),
+            )),
+            _ => Err(String::from(
+                "expected the incoming program, then the bank MSB, LSB and program",
+            )),
+        }
+    }
+}
+
+/// Parameters holding a persisted [`ProgramMap`], for the editor to edit it.
+pub trait WithProgramMap: Send + Sync {
+    fn program_map(&self) -> &RwLock<ProgramMap>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bank_goes_before_the_program() {
+        let target = BankProgram {
+            msb: 1,
+            lsb: 2,
+            program: 3,
+        };
+
+        let [msb, lsb, pc] = target.events(4, 5);
+
+        assert_eq!(msb, ControlChange::new(5, BANK_SELECT_MSB, 1).to_event(4));
+        assert_eq!(lsb, ControlChange::new(5, BANK_SELECT_LSB, 2).to_event(4));
+        assert_eq!(
+            pc,
+            NoteEvent::MidiProgramChange {
+                timing: 4,
+                channel: 5,
+                program: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn map_keeps_one_target_per_program() {
+        let mut map = ProgramMap::default();
+        let target = |program| BankProgram {
+            msb: 0,
+            lsb: 0,
+            program,
+        };
+
+        map.insert(10, target(20));
+        map.insert(10, target(30));
+
+        assert_eq!(map.get(10), Some(target(30)));
+        assert_eq!(map.get(11), None);
+
+        map.remove(10);
+
+        assert_eq!(map.get(10), None);
+    }
+
+    #[test]
+    fn entries_parse_as_typed_in_the_editor() {
+        let target = BankProgram {
+            msb: 0,
+            lsb: 1,
+            program: 12,
+        };
+
+        assert_eq!(
+            ProgramMap::parse_entry(" 5 0 1  12 "),
+            Ok((5, Some(target)))
+        );
+        assert_eq!(ProgramMap::parse_entry("5"), Ok((5, None)));
+        assert!(ProgramMap::parse_entry("5 0 1").is_err());
+        assert!(ProgramMap::parse_entry("5 0 1 128").is_err());
+        assert!(ProgramMap::parse_entry("").is_err());
+    }
+}
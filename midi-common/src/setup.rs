@@ -0,0 +1,398 @@
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::{ParamValue, PluginState};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// The version of the setup files written, files of a newer version are refused.
+pub const SETUP_FORMAT: u32 = 1;
+
+/// The complete state of a plugin in a file to share: the parameters by id and the persisted
+/// fields (program maps, setlists) as plain values. JSON, or TOML for a `.toml` file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Setup {
+    pub format: u32,
+    /// The name of the plugin that wrote it, a setup only loads in the same plugin.
+    pub plugin: String,
+    /// The version of the plugin that wrote it.
+    pub version: String,
+    pub params: BTreeMap<String, ParamValue>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, Value>,
+}
+
+/// A persisted field of a plugin, its value in a setup must deserialize as the type of the field.
+#[derive(Debug, Clone, Copy)]
+pub struct SetupField {
+    pub name: &'static str,
+    check: fn(&Value) -> bool,
+}
+
+impl SetupField {
+    pub const fn of<T: DeserializeOwned>(name: &'static str) -> Self {
+        Self {
+            name,
+            check: deserializes_as::<T>,
+        }
+    }
+}
+
+fn deserializes_as<T: DeserializeOwned>(value: &Value) -> bool {
+    T::deserialize(value).is_ok()
+}
+
+#[derive(Debug)]
+pub enum SetupError {
+    Io(std::io::Error),
+    Parse(String),
+    Toml(String),
+    Format(u32),
+    Plugin(String),
+    UnknownParam(String),
+    InvalidValue(String),
+    UnknownField(String),
+    InvalidField(String),
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Parse(error) => write!(f, "not a setup file: {error}"),
+            Self::Toml(error) => write!(f, "cannot be written as TOML: {error}"),
+            Self::Format(format) => write!(
+                f,
+                "written in format {format} by a newer version, this one reads up to format \
+                 {SETUP_FORMAT}"
+            ),
+            Self::Plugin(plugin) => write!(f, "this is a setup of {plugin}"),
+            Self::UnknownParam(id) => write!(f, "unknown parameter \"{id}\""),
+            Self::InvalidValue(id) => write!(f, "invalid value for the parameter \"{id}\""),
+            Self::UnknownField(name) => write!(f, "unknown field \"{name}\""),
+            Self::InvalidField(name) => write!(f, "invalid value for the field \"{name}\""),
+        }
+    }
+}
+
+impl From<std::io::Error> for SetupError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl Setup {
+    /// The setup of the plugin named `plugin` in `state`, as given by the GUI context.
+    pub fn from_state(plugin: &str, state: PluginState) -> Self {
+        Self {
+            format: SETUP_FORMAT,
+            plugin: plugin.to_owned(),
+            version: state.version,
+            params: state.params,
+            // the fields are serialized as JSON, kept as text when they are not
+            fields: state
+                .fields
+                .into_iter()
+                .map(|(name, text)| {
+                    let value = serde_json::from_str(&text).unwrap_or(Value::String(text));
+                    (name, value)
+                })
+                .collect(),
+        }
+    }
+
+    /// The state to give to the GUI context once checked against the parameters and the persisted
+    /// `fields` of the plugin named `plugin`.
+    pub fn into_state(
+        self,
+        plugin: &str,
+        params: &dyn Params,
+        fields: &[SetupField],
+    ) -> Result<PluginState, SetupError> {
+        if self.format > SETUP_FORMAT {
+            return Err(SetupError::Format(self.format));
+        }
+
+        if self.plugin != plugin {
+            return Err(SetupError::Plugin(self.plugin));
+        }
+
+        let map = params.param_map();
+        let mut values = BTreeMap::new();
+
+        for (id, value) in self.params {
+            let (_, ptr, _) = map
+                .iter()
+                .find(|(param_id, _, _)| *param_id == id)
+                .ok_or_else(|| SetupError::UnknownParam(id.clone()))?;
+
+            // a number reads as either type, it takes the one of the parameter
+            let value = match (ptr, value) {
+                (ParamPtr::FloatParam(_), ParamValue::F32(value)) => ParamValue::F32(value),
+                (ParamPtr::FloatParam(_), ParamValue::I32(value)) => ParamValue::F32(value as f32),
+                (ParamPtr::IntParam(_) | ParamPtr::EnumParam(_), ParamValue::I32(value)) => {
+                    ParamValue::I32(value)
+                }
+                (ParamPtr::IntParam(_) | ParamPtr::EnumParam(_), ParamValue::F32(value))
+                    if value.fract() == 0.0 =>
+                {
+                    ParamValue::I32(value as i32)
+                }
+                (ParamPtr::BoolParam(_), value @ ParamValue::Bool(_)) => value,
+                // a variant by name, restored by its index
+                (ParamPtr::EnumParam(_), ParamValue::String(text)) => {
+                    match unsafe { ptr.string_to_normalized_value(&text) } {
+                        Some(normalized) => {
+                            ParamValue::I32(unsafe { ptr.preview_plain(normalized) }.round() as i32)
+                        }
+                        None => return Err(SetupError::InvalidValue(id)),
+                    }
+                }
+                _ => return Err(SetupError::InvalidValue(id)),
+            };
+
+            // the host would clamp a value out of the range of the parameter without a word
+            if let ParamValue::I32(value) = value {
+                let plain = unsafe { ptr.preview_plain(ptr.preview_normalized(value as f32)) };
+
+                if plain.round() as i32 != value {
+                    return Err(SetupError::InvalidValue(id));
+                }
+            }
+
+            values.insert(id, value);
+        }
+
+        for (name, value) in &self.fields {
+            let field = fields
+                .iter()
+                .find(|field| field.name == name)
+                .ok_or_else(|| SetupError::UnknownField(name.clone()))?;
+
+            if !(field.check)(value) {
+                return Err(SetupError::InvalidField(name.clone()));
+            }
+        }
+
+        Ok(PluginState {
+            version: self.version,
+            params: values,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|(name, value)| (name, value.to_string()))
+                .collect(),
+        })
+    }
+
+    pub fn parse(text: &str, toml: bool) -> Result<Self, SetupError> {
+        if toml {
+            toml::from_str(text).map_err(|error| SetupError::Parse(error.to_string()))
+        } else {
+            serde_json::from_str(text).map_err(|error| SetupError::Parse(error.to_string()))
+        }
+    }
+
+    pub fn to_text(&self, toml: bool) -> Result<String, SetupError> {
+        if !toml {
+            let mut text = serde_json::to_string_pretty(self).expect("a setup is valid JSON");
+            text.push('\n');
+            return Ok(text);
+        }
+
+        // TOML has no null, the fields left out deserialize as `None`
+        let mut setup = self.clone();
+        setup.fields.values_mut().for_each(remove_nulls);
+
+        toml::to_string_pretty(&setup).map_err(|error| SetupError::Toml(error.to_string()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SetupError> {
+        Self::parse(&std::fs::read_to_string(path)?, is_toml(path))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SetupError> {
+        Ok(std::fs::write(path, self.to_text(is_toml(path))?)?)
+    }
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+}
+
+fn remove_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        Value::Array(values) => values.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::RwLock;
+
+    #[derive(Enum, PartialEq)]
+    enum Mode {
+        Slow,
+        Fast,
+    }
+
+    const FIELDS: &[SetupField] = &[SetupField::of::<BTreeMap<String, Option<u8>>>("map")];
+
+    #[derive(Params)]
+    struct TestParams {
+        #[id = "on"]
+        on: BoolParam,
+        #[id = "level"]
+        level: IntParam,
+        #[id = "mode"]
+        mode: EnumParam<Mode>,
+        #[persist = "map"]
+        map: RwLock<BTreeMap<String, Option<u8>>>,
+    }
+
+    impl Default for TestParams {
+        fn default() -> Self {
+            Self {
+                on: BoolParam::new("On", false),
+                level: IntParam::new("Level", 5, IntRange::Linear { min: 0, max: 10 }),
+                mode: EnumParam::new("Mode", Mode::Slow),
+                map: RwLock::new(BTreeMap::new()),
+            }
+        }
+    }
+
+    fn setup() -> Setup {
+        Setup::from_state(
+            "test",
+            PluginState {
+                version: "1.0.0".to_owned(),
+                params: BTreeMap::from([
+                    ("on".to_owned(), ParamValue::Bool(true)),
+                    ("level".to_owned(), ParamValue::I32(7)),
+                ]),
+                fields: BTreeMap::from([("map".to_owned(), r#"{"a":1,"b":null}"#.to_owned())]),
+            },
+        )
+    }
+
+    #[test]
+    fn json_and_toml_round_trip() {
+        let setup = setup();
+        assert_eq!(setup.fields["map"]["a"], 1);
+
+        let params = TestParams::default();
+
+        let json = setup.to_text(false).unwrap();
+        let from_json = Setup::parse(&json, false).unwrap();
+        assert_eq!(from_json.fields, setup.fields);
+        assert_eq!(
+            from_json
+                .into_state("test", &params, FIELDS)
+                .unwrap()
+                .params,
+            setup.params
+        );
+
+        let toml = setup.to_text(true).unwrap();
+        let from_toml = Setup::parse(&toml, true).unwrap();
+        assert_eq!(from_toml.fields["map"], serde_json::json!({ "a": 1 }));
+
+        let state = from_toml.into_state("test", &params, FIELDS).unwrap();
+        assert_eq!(state.params, setup.params);
+        assert_eq!(state.fields["map"], r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn variants_by_name_are_restored_by_index() {
+        let mut setup = setup();
+        setup
+            .params
+            .insert("mode".to_owned(), ParamValue::String("Fast".to_owned()));
+
+        let state = setup
+            .into_state("test", &TestParams::default(), FIELDS)
+            .unwrap();
+        assert_eq!(state.params["mode"], ParamValue::I32(1));
+    }
+
+    #[test]
+    fn invalid_setups_are_explained() {
+        let params = TestParams::default();
+        let error = |setup: Setup| {
+            setup
+                .into_state("test", &params, FIELDS)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error(Setup {
+                format: SETUP_FORMAT + 1,
+                ..setup()
+            }),
+            "written in format 2 by a newer version, this one reads up to format 1"
+        );
+
+        assert_eq!(
+            error(Setup {
+                plugin: "other".to_owned(),
+                ..setup()
+            }),
+            "this is a setup of other"
+        );
+
+        let mut wrong_type = setup();
+        wrong_type
+            .params
+            .insert("level".to_owned(), ParamValue::Bool(true));
+        assert_eq!(
+            error(wrong_type),
+            "invalid value for the parameter \"level\""
+        );
+
+        let mut out_of_range = setup();
+        out_of_range
+            .params
+            .insert("level".to_owned(), ParamValue::I32(11));
+        assert_eq!(
+            error(out_of_range),
+            "invalid value for the parameter \"level\""
+        );
+
+        let mut unknown_variant = setup();
+        unknown_variant
+            .params
+            .insert("mode".to_owned(), ParamValue::String("Medium".to_owned()));
+        assert_eq!(
+            error(unknown_variant),
+            "invalid value for the parameter \"mode\""
+        );
+
+        let mut unknown = setup();
+        unknown.fields.insert("scenes".to_owned(), Value::Null);
+        assert_eq!(error(unknown), "unknown field \"scenes\"");
+
+        let mut wrong_field = setup();
+        wrong_field
+            .fields
+            .insert("map".to_owned(), serde_json::json!({ "a": "one" }));
+        assert_eq!(error(wrong_field), "invalid value for the field \"map\"");
+
+        assert!(
+            Setup::parse("{ \"format\": 1 }", false)
+                .unwrap_err()
+                .to_string()
+                .starts_with("not a setup file: missing field `plugin`")
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VelocityCurve {
+    #[name = "Linear"]
+    Linear,
+
+    /// Louder at low velocities.
+    #[name = "Soft"]
+    Soft,
+
+    /// Quieter at low velocities.
+    #[name = "Hard"]
+    Hard,
+
+    #[name = "S-Curve"]
+    SCurve,
+
+    #[name = "Fixed"]
+    Fixed,
+}
+
+/// The velocity mapping of a patch: the curve, then the min / max clamp. Values are 7-bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VelocityTransform {
+    pub curve: VelocityCurve,
+    pub fixed: u8,
+    pub min: u8,
+    pub max: u8,
+}
+
+impl Default for VelocityTransform {
+    fn default() -> Self {
+        Self {
+            curve: VelocityCurve::Linear,
+            fixed: 100,
+            min: 1,
+            max: 127,
+        }
+    }
+}
+
+impl VelocityTransform {
+    /// Maps a normalized velocity.
+    pub fn apply(self, velocity: f32) -> f32 {
+        let x = velocity.clamp(0.0, 1.0);
+
+        let mapped = match self.curve {
+            VelocityCurve::Linear => x,
+            VelocityCurve::Soft => x.sqrt(),
+            VelocityCurve::Hard => x * x,
+            VelocityCurve::SCurve => x * x * (3.0 - 2.0 * x),
+            VelocityCurve::Fixed => self.fixed as f32 / 127.0,
+        };
+
+        let min = self.min.min(self.max) as f32 / 127.0;
+        let max = self.max.max(self.min) as f32 / 127.0;
+
+        mapped.clamp(min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(curve: VelocityCurve) -> VelocityTransform {
+        VelocityTransform {
+            curve,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn curves_keep_the_ends() {
+        for curve in [
+            VelocityCurve::Linear,
+            VelocityCurve::Soft,
+            VelocityCurve::Hard,
+            VelocityCurve::SCurve,
+        ] {
+            assert_eq!(transform(curve).apply(1.0), 1.0);
+            assert_eq!(transform(curve).apply(0.0), 1.0 / 127.0);
+        }
+
+        assert!(transform(VelocityCurve::Soft).apply(0.5) > 0.5);
+        assert!(transform(VelocityCurve::Hard).apply(0.5) < 0.5);
+    }
+
+    #[test]
+    fn fixed_and_clamp() {
+        assert_eq!(transform(VelocityCurve::Fixed).apply(0.2), 100.0 / 127.0);
+
+        let clamped = VelocityTransform {
+            min: 64,
+            max: 32,
+            ..Default::default()
+        };
+
+        assert_eq!(clamped.apply(0.0), 32.0 / 127.0);
+        assert_eq!(clamped.apply(1.0), 64.0 / 127.0);
+    }
+}
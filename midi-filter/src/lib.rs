@@ -1,21 +1,31 @@
+mod presets;
+
+use midi_common::editor::{self, ViziaState};
+use midi_common::{
+    is_lsb_controller, logical_controller, lsb_controller, EventMonitor, MonitoredContext,
+};
 use nih_plug::{
     midi::control_change::{
-        BREATH_CONTROLLER_LSB, BREATH_CONTROLLER_MSB, DAMPER_PEDAL, DATA_ENTRY_LSB, DATA_ENTRY_MSB,
-        EXPRESSION_CONTROLLER_LSB, EXPRESSION_CONTROLLER_MSB, FOOT_CONTROLLER_LSB,
-        FOOT_CONTROLLER_MSB, MODULATION_LSB, MODULATION_MSB,
+        BREATH_CONTROLLER_MSB, DAMPER_PEDAL, DATA_ENTRY_MSB, EXPRESSION_CONTROLLER_MSB,
+        FOOT_CONTROLLER_MSB, MODULATION_MSB,
     },
     prelude::*,
 };
 use std::sync::Arc;
 
-struct MidiFilter {
+pub struct MidiFilter {
+    monitor: Arc<EventMonitor>,
     params: Arc<MidiFilterParams>,
+    /// Samples processed since the plugin was activated, the time of the monitor.
+    position: u64,
 }
 
 impl Default for MidiFilter {
     fn default() -> Self {
         Self {
+            monitor: Default::default(),
             params: Arc::new(MidiFilterParams::default()),
+            position: 0,
         }
     }
 }
@@ -41,29 +51,52 @@ impl Plugin for MidiFilter {
         self.params.clone()
     }
 
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            Self::NAME,
+            self.params.clone(),
+            presets::FACTORY_PRESETS,
+            self.monitor.clone(),
+            self.params.editor_state.clone(),
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.monitor.set_sample_rate(buffer_config.sample_rate);
+        true
+    }
+
     fn process(
         &mut self,
-        _buffer: &mut Buffer,
+        buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        let context = &mut MonitoredContext::<Self, _>::new(context, &self.monitor, self.position);
+        self.position += buffer.samples() as u64;
+
         while let Some(event) = context.next_event() {
             match event {
-                event @ NoteEvent::MidiCC { cc, .. } => {
-                    let allow = match cc {
-                        BREATH_CONTROLLER_MSB | BREATH_CONTROLLER_LSB => self.params.bc.value(),
-                        DATA_ENTRY_MSB | DATA_ENTRY_LSB => self.params.de.value(),
-                        DAMPER_PEDAL => self.params.dp.value(),
-                        EXPRESSION_CONTROLLER_MSB | EXPRESSION_CONTROLLER_LSB => {
-                            self.params.ex.value()
+                NoteEvent::MidiCC {
+                    timing,
+                    channel,
+                    cc,
+                    value,
+                } => {
+                    if self.params.allows(cc) {
+                        if let Some(cc) = self.params.remap(cc) {
+                            context.send_event(NoteEvent::MidiCC {
+                                timing,
+                                channel,
+                                cc,
+                                value,
+                            });
                         }
-                        FOOT_CONTROLLER_MSB | FOOT_CONTROLLER_LSB => self.params.fp.value(),
-                        MODULATION_MSB | MODULATION_LSB => self.params.mw.value(),
-                        _ => true,
-                    };
-
-                    if allow {
-                        context.send_event(event);
                     }
                 }
                 event @ NoteEvent::MidiPitchBend { .. } => {
@@ -81,6 +114,9 @@ impl Plugin for MidiFilter {
 
 #[derive(Params)]
 struct MidiFilterParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+
     /// Breath Controller
     #[id = "bc"]
     bc: BoolParam,
@@ -108,11 +144,20 @@ struct MidiFilterParams {
     /// Pitch-Bend
     #[id = "pb"]
     pb: BoolParam,
+
+    /// Controller to remap, -1 to disable. The LSB of a 14-bit pair selects the pair.
+    #[id = "rf"]
+    remap_from: IntParam,
+
+    /// Controller receiving the remapped messages
+    #[id = "rt"]
+    remap_to: IntParam,
 }
 
 impl Default for MidiFilterParams {
     fn default() -> Self {
         Self {
+            editor_state: editor::default_state(),
             bc: BoolParam::new("Breath Controller", false),
             dp: BoolParam::new("Sustain Pedal", false),
             de: BoolParam::new("Data Entry", false),
@@ -120,10 +165,51 @@ impl Default for MidiFilterParams {
             ex: BoolParam::new("Expression", false),
             mw: BoolParam::new("Mod Wheel", false),
             pb: BoolParam::new("Pitch Bend", false),
+            remap_from: IntParam::new("Remap From", -1, IntRange::Linear { min: -1, max: 119 }),
+            remap_to: IntParam::new("Remap To", 0, IntRange::Linear { min: 0, max: 119 }),
         }
     }
 }
 
+impl MidiFilterParams {
+    /// Whether the controller `cc` goes through, both halves of a 14-bit pair are filtered
+    /// together.
+    fn allows(&self, cc: u8) -> bool {
+        match logical_controller(cc) {
+            BREATH_CONTROLLER_MSB => self.bc.value(),
+            DATA_ENTRY_MSB => self.de.value(),
+            DAMPER_PEDAL => self.dp.value(),
+            EXPRESSION_CONTROLLER_MSB => self.ex.value(),
+            FOOT_CONTROLLER_MSB => self.fp.value(),
+            MODULATION_MSB => self.mw.value(),
+            _ => true,
+        }
+    }
+
+    /// The controller `cc` is sent on, see [`remap`].
+    fn remap(&self, cc: u8) -> Option<u8> {
+        let from = self.remap_from.value();
+
+        if from < 0 {
+            Some(cc)
+        } else {
+            remap(cc, from as u8, self.remap_to.value().clamp(0, 119) as u8)
+        }
+    }
+}
+
+/// The controller `cc` is sent on when `from` is remapped to `to`. The LSB of a remapped pair
+/// follows its MSB and is dropped when the target has no LSB.
+fn remap(cc: u8, from: u8, to: u8) -> Option<u8> {
+    if logical_controller(cc) != logical_controller(from) {
+        Some(cc)
+    } else if is_lsb_controller(cc) {
+        lsb_controller(to)
+    } else {
+        Some(to)
+    }
+}
+
 impl ClapPlugin for MidiFilter {
     const CLAP_ID: &'static str = "com.moist-plugins-gmbh.midi-filter";
     const CLAP_DESCRIPTION: Option<&'static str> = Some("Filter midi message.");
@@ -140,3 +226,42 @@ impl Vst3Plugin for MidiFilter {
 
 nih_export_clap!(MidiFilter);
 nih_export_vst3!(MidiFilter);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nih_plug::midi::control_change::MAIN_VOLUME_MSB;
+
+    #[test]
+    fn remapping_an_msb_moves_its_lsb() {
+        assert_eq!(remap(1, 1, 11), Some(11));
+        assert_eq!(remap(33, 1, 11), Some(43));
+        assert_eq!(remap(2, 1, 11), Some(2));
+        assert_eq!(remap(34, 1, 11), Some(34));
+    }
+
+    #[test]
+    fn an_lsb_alone_is_remapped_to_the_paired_lsb() {
+        assert_eq!(remap(33, 33, 11), Some(43));
+        assert_eq!(remap(1, 33, 11), Some(11));
+        // controllers from 32 on have no LSB
+        assert_eq!(remap(33, 1, 64), None);
+    }
+
+    #[test]
+    fn filtering_an_msb_filters_its_lsb() {
+        let params = MidiFilterParams::default();
+
+        for cc in [
+            MODULATION_MSB,
+            BREATH_CONTROLLER_MSB,
+            EXPRESSION_CONTROLLER_MSB,
+        ] {
+            assert!(!params.allows(cc));
+            assert!(!params.allows(lsb_controller(cc).unwrap()));
+        }
+
+        assert!(params.allows(MAIN_VOLUME_MSB));
+        assert!(params.allows(lsb_controller(MAIN_VOLUME_MSB).unwrap()));
+    }
+}
@@ -0,0 +1,6 @@
+use midi_filter::MidiFilter;
+use nih_plug::prelude::*;
+
+fn main() {
+    nih_export_standalone::<MidiFilter>();
+}
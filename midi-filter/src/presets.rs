@@ -0,0 +1,23 @@
+/// The factory presets, stepped through in the editor.
+pub const FACTORY_PRESETS: &[&str] = &[
+    include_str!("../presets/strip-all-but-notes.json"),
+    include_str!("../presets/pass-everything.json"),
+    include_str!("../presets/sustain-only.json"),
+    include_str!("../presets/breath-to-mod-wheel.json"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MidiFilterParams;
+    use midi_common::check_presets;
+
+    #[test]
+    fn factory_presets_are_valid() {
+        let presets = check_presets(FACTORY_PRESETS, &MidiFilterParams::default()).unwrap();
+        let files = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/presets")).unwrap();
+
+        // every file of the directory is listed
+        assert_eq!(presets.len(), files.count());
+    }
+}
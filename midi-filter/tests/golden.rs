@@ -0,0 +1,6 @@
+use midi_cli::golden_case;
+
+#[test]
+fn strips_breath_controller() {
+    golden_case(env!("CARGO_MANIFEST_DIR"), "strip-breath");
+}
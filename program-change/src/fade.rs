@@ -0,0 +1,222 @@
+use nih_plug::{
+    midi::control_change::{EXPRESSION_CONTROLLER_MSB, MAIN_VOLUME_MSB},
+    prelude::*,
+};
+
+/// Samples between two controller messages of a ramp.
+const RAMP_STEP: u32 = 64;
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FadeController {
+    #[name = "Off"]
+    Off,
+
+    #[name = "Volume"]
+    Volume,
+
+    #[name = "Expression"]
+    Expression,
+}
+
+impl FadeController {
+    pub fn cc(self) -> Option<u8> {
+        match self {
+            Self::Off => None,
+            Self::Volume => Some(MAIN_VOLUME_MSB),
+            Self::Expression => Some(EXPRESSION_CONTROLLER_MSB),
+        }
+    }
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FadeCurve {
+    #[name = "Linear"]
+    Linear,
+
+    #[name = "Slow Start"]
+    SlowStart,
+
+    #[name = "Fast Start"]
+    FastStart,
+
+    #[name = "S-Curve"]
+    SCurve,
+}
+
+impl FadeCurve {
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            Self::Linear => x,
+            Self::SlowStart => x * x,
+            Self::FastStart => 1.0 - (1.0 - x) * (1.0 - x),
+            Self::SCurve => x * x * (3.0 - 2.0 * x),
+        }
+    }
+}
+
+/// When the voices held after a deactivation are released.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FadeRelease {
+    #[name = "Keys Up"]
+    KeysUp,
+
+    #[name = "Fade End"]
+    FadeEnd,
+}
+
+/// A controller moving from one value to another over a number of samples.
+pub struct Ramp {
+    pub channel: u8,
+    pub cc: u8,
+    pub release_when_done: bool,
+    curve: FadeCurve,
+    from: u8,
+    last: Option<u8>,
+    length: u32,
+    position: u32,
+    to: u8,
+}
+
+impl Ramp {
+    pub fn new(channel: u8, cc: u8, from: u8, to: u8, length: u32, curve: FadeCurve) -> Self {
+        Self {
+            channel,
+            cc,
+            release_when_done: false,
+            curve,
+            from,
+            last: None,
+            length,
+            position: 0,
+            to,
+        }
+    }
+
+    /// The value last sent, or where the ramp starts when nothing was sent yet.
+    pub fn current(&self) -> u8 {
+        self.last.unwrap_or(self.from)
+    }
+
+    pub fn target(&self) -> u8 {
+        self.to
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.position >= self.length && self.last == Some(self.to)
+    }
+
+    /// Sends the values of the ramp falling in the next `samples`.
+    pub fn process<P: Plugin<SysExMessage = ()>>(
+        &mut self,
+        samples: u32,
+        ctx: &mut impl ProcessContext<P>,
+    ) {
+        let mut timing = 0;
+
+        while timing < samples && !self.is_done() {
+            let value = self.value();
+
+            if self.last != Some(value) {
+                ctx.send_event(NoteEvent::MidiCC {
+                    timing,
+                    channel: self.channel,
+                    cc: self.cc,
+                    value: value as f32 / 127.0,
+                });
+
+                self.last = Some(value);
+            }
+
+            let step = RAMP_STEP.min(samples - timing);
+
+            timing += step;
+            self.position = (self.position + step).min(self.length);
+        }
+    }
+
+    fn value(&self) -> u8 {
+        let progress = if self.length == 0 {
+            1.0
+        } else {
+            self.position as f32 / self.length as f32
+        };
+
+        let from = self.from as f32;
+        let to = self.to as f32;
+
+        (from + (to - from) * self.curve.apply(progress)).round() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TestContext;
+
+    /// The values sent by `ramp` over the next `samples`, with their timing.
+    fn run(ramp: &mut Ramp, samples: u32) -> Vec<(u32, u8)> {
+        let mut ctx = TestContext::default();
+        ramp.process(samples, &mut ctx);
+
+        ctx.output
+            .iter()
+            .map(|event| match *event {
+                NoteEvent::MidiCC {
+                    timing,
+                    channel: 2,
+                    cc: MAIN_VOLUME_MSB,
+                    value,
+                } => (timing, (value * 127.0).round() as u8),
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn each_curve_ramps_from_start_to_end() {
+        for (curve, middle) in [
+            (FadeCurve::Linear, 64),
+            (FadeCurve::SlowStart, 32),
+            (FadeCurve::FastStart, 95),
+            (FadeCurve::SCurve, 64),
+        ] {
+            let mut ramp = Ramp::new(2, MAIN_VOLUME_MSB, 0, 127, 10 * RAMP_STEP, curve);
+            let sent = run(&mut ramp, 20 * RAMP_STEP);
+
+            assert_eq!(sent.first(), Some(&(0, 0)), "{curve:?}");
+            assert_eq!(sent.last(), Some(&(10 * RAMP_STEP, 127)), "{curve:?}");
+            assert!(sent.contains(&(5 * RAMP_STEP, middle)), "{curve:?}");
+            assert!(
+                sent.windows(2).all(|pair| pair[0].1 < pair[1].1),
+                "{curve:?}"
+            );
+            assert!(ramp.is_done());
+        }
+    }
+
+    #[test]
+    fn ramp_goes_on_over_the_blocks() {
+        let mut ramp = Ramp::new(2, MAIN_VOLUME_MSB, 100, 0, 4 * RAMP_STEP, FadeCurve::Linear);
+        assert_eq!(ramp.current(), 100);
+
+        assert_eq!(run(&mut ramp, 2 * RAMP_STEP), [(0, 100), (RAMP_STEP, 75)]);
+        assert_eq!(ramp.current(), 75);
+        assert!(!ramp.is_done());
+
+        // the end value is sent even when the last block ends exactly with the ramp
+        assert_eq!(run(&mut ramp, 2 * RAMP_STEP), [(0, 50), (RAMP_STEP, 25)]);
+        assert!(!ramp.is_done());
+        assert_eq!(run(&mut ramp, RAMP_STEP), [(0, 0)]);
+        assert!(ramp.is_done());
+        assert_eq!(ramp.current(), 0);
+        assert_eq!(run(&mut ramp, RAMP_STEP), []);
+    }
+
+    #[test]
+    fn empty_ramp_sends_its_target_at_once() {
+        let mut ramp = Ramp::new(2, MAIN_VOLUME_MSB, 0, 90, 0, FadeCurve::SCurve);
+
+        assert_eq!(run(&mut ramp, RAMP_STEP), [(0, 90)]);
+        assert!(ramp.is_done());
+    }
+}
@@ -1,3 +1,19 @@
+mod fade;
+mod presets;
+mod range;
+mod setlist;
+
+use fade::{FadeController, FadeCurve, FadeRelease, Ramp};
+use midi_common::{
+    controller_value,
+    editor::{self, ViziaState},
+    event_note, fine_param, input_channel, input_channel_param, mpe_members, next_input_event,
+    nrpn_param_change, transpose_semitones, velocity_param, velocity_transform, with_channel,
+    ControllerValue, DamperState, EventMonitor, HoldPolicies, HoldPolicy, MonitoredContext,
+    NoteMap, NotesState, ParameterChange, ProgramChangePolicy, ProgramMap, SetupField,
+    StateOverlay, StateView, VelocityCurve, VelocityTransform, WithProgramMap, MAX_14_BIT,
+    MPE_MANAGER_CHANNEL,
+};
 use nih_plug::{
     midi::control_change::{
         BANK_SELECT_LSB, BANK_SELECT_MSB, DAMPER_PEDAL, EXPRESSION_CONTROLLER_MSB,
@@ -6,19 +22,200 @@ use nih_plug::{
     },
     prelude::*,
 };
-use std::{mem::take, sync::Arc};
+use range::{KeyRange, OutsideRange};
+use serde::{Deserialize, Serialize};
+use setlist::{SetlistEntry, SetlistTask, Step, Trigger};
+use std::{
+    mem::take,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
 
-struct ProgramChange {
+pub struct ProgramChange {
+    add_trigger: Trigger,
+    deferred: u32,
+    entry: Option<ParamsSnapshot>,
+    /// The crossfade ramps, one per channel and controller. Finished ones stay until replaced.
+    fades: Vec<Ramp>,
+    held: NoteMap,
+    monitor: Arc<EventMonitor>,
+    next_trigger: Trigger,
+    overlay: Arc<StateOverlay<ParamsSnapshot>>,
+    pool_cursor: u8,
+    /// Samples processed since the plugin was activated, the time of the monitor.
+    position: u64,
+    previous_trigger: Trigger,
+    sample_rate: f32,
     state: InternalState,
+    store_trigger: Trigger,
     params: Arc<ProgramChangeParams>,
 }
 
 impl Default for ProgramChange {
     fn default() -> Self {
         Self {
+            add_trigger: Default::default(),
+            deferred: 0,
+            entry: None,
+            // at most one ramp per channel and controller, so the audio thread never allocates
+            fades: Vec::with_capacity(16 * 2),
+            held: NoteMap::default(),
+            monitor: Default::default(),
+            next_trigger: Default::default(),
+            overlay: Default::default(),
+            pool_cursor: 0,
+            position: 0,
+            previous_trigger: Default::default(),
             params: Arc::new(ProgramChangeParams::default()),
+            sample_rate: 44100.0,
             state: InternalState::Off,
+            store_trigger: Default::default(),
+        }
+    }
+}
+
+impl ProgramChange {
+    /// Ramps the faded controller of `snapshot` to its level, starting where the running ramp
+    /// of the same controller is or from `from`.
+    fn fade_in(&mut self, snapshot: &ParamsSnapshot, from: u8) {
+        if let Some((cc, level)) = snapshot.faded_level() {
+            match self.running_ramp(snapshot.ch, cc) {
+                Some(ramp) if ramp.target() == level && !ramp.release_when_done => {}
+                Some(ramp) => self.start(self.ramp(snapshot.ch, cc, ramp.current(), level)),
+                None => self.start(self.ramp(snapshot.ch, cc, from, level)),
+            }
+        }
+    }
+
+    /// Moves the patch to the next free channel of the pool. When every channel is still
+    /// sounding, the notes of the next one are cut.
+    fn allocate(
+        &mut self,
+        snapshot: &mut ParamsSnapshot,
+        notes: &mut NotesState,
+        ctx: &mut impl ProcessContext<Self>,
+    ) {
+        if snapshot.pool <= 1 {
+            return;
+        }
+
+        let pool = snapshot.pool;
+        let offset = (1..=pool)
+            .map(|step| (self.pool_cursor + step) % pool)
+            .find(|offset| notes.is_channel_off((snapshot.base + offset) % 16))
+            .unwrap_or((self.pool_cursor + 1) % pool);
+
+        self.pool_cursor = offset;
+        snapshot.ch = (snapshot.base + offset) % 16;
+
+        notes.take_channel(snapshot.ch).send_all_note_off(ctx);
+    }
+
+    /// Whether sending `new` waits for silence, counting the samples it has been waiting.
+    fn defers(
+        &mut self,
+        new: &ParamsSnapshot,
+        old: &ParamsSnapshot,
+        silent: bool,
+        samples: u32,
+    ) -> bool {
+        let timeout = self.params.defer_timeout.value() / 1000.0 * self.sample_rate;
+
+        let defers = self.params.defer.value()
+            && !silent
+            && new.changes_program(old)
+            && (timeout <= 0.0 || (self.deferred as f32) < timeout);
+
+        self.deferred = if defers { self.deferred + samples } else { 0 };
+        defers
+    }
+
+    fn ramp(&self, channel: u8, cc: u8, from: u8, to: u8) -> Ramp {
+        let length = self.params.fade_time.value() / 1000.0 * self.sample_rate;
+        let curve = self.params.fade_curve.value();
+
+        Ramp::new(channel, cc, from, to, length as u32, curve)
+    }
+
+    /// Ramps the faded controller of `snapshot` down to silence.
+    fn fade_out(&mut self, snapshot: &ParamsSnapshot) {
+        if let Some((cc, level)) = snapshot.faded_level() {
+            let from = match self.running_ramp(snapshot.ch, cc) {
+                Some(ramp) if ramp.target() == 0 => return,
+                Some(ramp) => ramp.current(),
+                None => level,
+            };
+
+            let mut ramp = self.ramp(snapshot.ch, cc, from, 0);
+            ramp.release_when_done = self.params.fade_release.value() == FadeRelease::FadeEnd;
+            self.start(ramp);
+        }
+    }
+
+    fn running_ramp(&self, channel: u8, cc: u8) -> Option<&Ramp> {
+        self.fades
+            .iter()
+            .find(|ramp| ramp.channel == channel && ramp.cc == cc)
+    }
+
+    /// Starts `ramp` in place of the one of the same channel and controller.
+    fn start(&mut self, ramp: Ramp) {
+        match self
+            .fades
+            .iter_mut()
+            .find(|running| running.channel == ramp.channel && running.cc == ramp.cc)
+        {
+            Some(running) => *running = ramp,
+            None => self.fades.push(ramp),
+        }
+    }
+
+    /// Drops the ramps of `channel`, a patch sent to it fades in from silence.
+    fn stop_fades(&mut self, channel: u8) {
+        self.fades.retain(|ramp| ramp.channel != channel);
+    }
+
+    /// Handles the setlist trigger parameters, the edits run in the background.
+    fn poll_triggers(&mut self, ctx: &mut impl ProcessContext<Self>) {
+        let position = self.params.setlist_position.load(Ordering::Relaxed);
+
+        if self.next_trigger.rises(self.params.next.value()) {
+            self.params.step(Step::Next);
+        }
+
+        if self.previous_trigger.rises(self.params.previous.value()) {
+            self.params.step(Step::Previous);
+        }
+
+        if self.store_trigger.rises(self.params.store.value()) {
+            let snapshot = self.params.snapshot();
+            ctx.execute_background(SetlistTask::Store { position, snapshot });
+        }
+
+        if self.add_trigger.rises(self.params.add.value()) {
+            let snapshot = self.params.snapshot();
+            ctx.execute_background(SetlistTask::Insert { position, snapshot });
+        }
+    }
+
+    /// The snapshot to play: the current setlist entry in setlist mode, the parameters
+    /// otherwise.
+    fn target(&mut self) -> ParamsSnapshot {
+        if !self.params.setlist_mode.value() {
+            return self.params.snapshot();
+        }
+
+        // the last entry stays while the setlist is being edited
+        if let Ok(entries) = self.params.setlist.try_read() {
+            let position = self.params.setlist_position.load(Ordering::Relaxed);
+            self.entry = entries
+                .get(position.min(entries.len().saturating_sub(1)))
+                .map(|entry| entry.snapshot);
         }
+
+        self.entry.unwrap_or_else(|| self.params.snapshot())
     }
 }
 
@@ -37,91 +234,255 @@ impl Plugin for ProgramChange {
     const SAMPLE_ACCURATE_AUTOMATION: bool = false;
 
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = SetlistTask;
 
     fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
-        None
+        editor::create_with_state(
+            Self::NAME,
+            self.params.clone(),
+            presets::FACTORY_PRESETS,
+            FIELDS,
+            self.monitor.clone(),
+            self.overlay.clone(),
+            self.params.editor_state.clone(),
+        )
     }
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        Box::new(move |task| task.run(&params.setlist, &params.setlist_position))
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+        self.monitor.set_sample_rate(buffer_config.sample_rate);
+        true
+    }
+
     fn process(
         &mut self,
-        _buffer: &mut Buffer,
+        buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
         ctx: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        self.process_block(buffer.samples() as u32, ctx);
+        ProcessStatus::Normal
+    }
+}
+
+impl ProgramChange {
+    /// Runs the state machine and the events of `ctx` over the next `samples`.
+    fn process_block(&mut self, samples: u32, ctx: &mut impl ProcessContext<Self>) {
         const OFF: bool = false;
         const ON: bool = true;
 
+        let position = self.position;
+        let monitor = self.monitor.clone();
+        let ctx = &mut MonitoredContext::<Self, _>::new(ctx, &monitor, position);
+        let previous_state = self.state.name();
+
+        self.position += samples as u64;
+        self.poll_triggers(ctx);
+        let target = self.target();
+
         self.state = match (take(&mut self.state), self.params.active.value()) {
             (InternalState::Off, OFF) => InternalState::Off,
 
             (InternalState::Off, ON) => {
-                let snapshot = self.params.snapshot();
+                let mut snapshot = target;
+                let mut notes = NotesState::default();
 
+                self.allocate(&mut snapshot, &mut notes, ctx);
                 snapshot.send(None, ctx);
+                self.stop_fades(snapshot.ch);
+                self.fade_in(&snapshot, 0);
 
                 InternalState::On {
-                    notes: Default::default(),
+                    damper: Default::default(),
+                    notes,
                     snapshot,
                 }
             }
 
+            (
+                InternalState::SeamlessSwitch {
+                    damper,
+                    mut notes,
+                    snapshot,
+                },
+                ON,
+            ) if snapshot.pool > 1 && snapshot.same_pool(&target) => {
+                // the held notes keep ringing on their channel, the patch goes to the next one
+                let mut new = target;
+
+                self.allocate(&mut new, &mut notes, ctx);
+                new.send(None, ctx);
+                self.fade_in(&new, 0);
+
+                InternalState::On {
+                    damper,
+                    notes,
+                    snapshot: new,
+                }
+            }
+
             (
                 InternalState::On {
+                    damper,
                     mut notes,
                     snapshot,
                 }
                 | InternalState::SeamlessSwitch {
+                    damper,
                     mut notes,
                     snapshot,
                 },
                 ON,
             ) => {
-                let new = self.params.snapshot();
+                let mut new = target;
+                let silent = notes.is_all_off() && damper.is_off();
+                let setlist = self.params.setlist_mode.value();
 
-                if new.ch == snapshot.ch {
-                    new.send(Some(&snapshot), ctx);
-                } else {
-                    notes.send_all_note_off(snapshot.ch, ctx);
-                    new.send(None, ctx);
+                if snapshot.same_pool(&new) {
+                    new.ch = snapshot.ch;
 
-                    notes = Default::default();
+                    // a setlist step holds the ringing notes like a reactivation
+                    if setlist && new.changes_program(&snapshot) {
+                        self.allocate(&mut new, &mut notes, ctx);
+                    }
                 }
 
-                InternalState::On {
-                    notes,
-                    snapshot: new,
+                if new.ch == snapshot.ch && self.defers(&new, &snapshot, silent, samples) {
+                    // the synth keeps the current patch until the ringing notes are released
+                    InternalState::On {
+                        damper,
+                        notes,
+                        snapshot,
+                    }
+                } else {
+                    if new.ch == snapshot.ch {
+                        new.send(Some(&snapshot), ctx);
+                        self.fade_in(&new, snapshot.level_of(new.fade));
+                    } else {
+                        if !setlist {
+                            notes.send_all_note_off(ctx);
+                            notes = Default::default();
+                        }
+
+                        new.send(None, ctx);
+
+                        self.stop_fades(new.ch);
+                        self.fade_in(&new, 0);
+
+                        if !snapshot.same_pool(&new) {
+                            self.pool_cursor = 0;
+                        }
+                    }
+
+                    InternalState::On {
+                        damper,
+                        notes,
+                        snapshot: new,
+                    }
                 }
             }
 
             (
-                InternalState::On { notes, snapshot }
-                | InternalState::SeamlessSwitch { notes, snapshot },
+                InternalState::On {
+                    mut damper,
+                    notes,
+                    snapshot,
+                }
+                | InternalState::SeamlessSwitch {
+                    mut damper,
+                    notes,
+                    snapshot,
+                },
                 OFF,
             ) => {
-                damper_off(ctx, snapshot.ch);
+                for channel in snapshot.pool_channels() {
+                    damper_off(ctx, channel);
+                }
+
+                damper.set_off();
+                self.deferred = 0;
 
                 if notes.is_all_off() {
                     InternalState::Off
-                } else if self.params.channel() != snapshot.ch {
-                    notes.send_all_note_off(snapshot.ch, ctx);
+                } else if !snapshot.same_pool(&target) {
+                    notes.send_all_note_off(ctx);
                     InternalState::Off
                 } else {
-                    InternalState::SeamlessSwitch { notes, snapshot }
+                    self.fade_out(&snapshot);
+                    InternalState::SeamlessSwitch {
+                        damper,
+                        notes,
+                        snapshot,
+                    }
                 }
             }
         };
 
+        for ramp in &mut self.fades {
+            ramp.process(samples, ctx);
+
+            // the voices held through the switch are released once faded out, those of the
+            // faded channel only when the patch already moved to another channel of the pool
+            if ramp.release_when_done && ramp.is_done() {
+                match &mut self.state {
+                    InternalState::SeamlessSwitch { notes, .. } => {
+                        notes.send_all_note_off(ctx);
+                        self.state = InternalState::Off;
+                    }
+                    InternalState::On { notes, .. } => {
+                        notes.take_channel(ramp.channel).send_all_note_off(ctx);
+                    }
+                    InternalState::Off => {}
+                }
+
+                ramp.release_when_done = false;
+            }
+        }
+
+        if self.state.name() != previous_state {
+            monitor.state(position, self.state.name());
+        }
+
+        let input = self.params.input_channel();
+        let pass_other = self.params.pass_other.value();
+
         match &mut self.state {
-            InternalState::Off => {}
-            InternalState::On { notes, snapshot } => {
-                let channel = snapshot.ch;
+            InternalState::Off => {
+                // only the events of the other channels go through while inactive
+                while next_input_event(input, pass_other, ctx).is_some() {}
+            }
+            InternalState::On {
+                damper,
+                notes,
+                snapshot,
+            } => {
+                while let Some(event) = next_input_event(input, pass_other, ctx) {
+                    if self.params.steps_on(&event) {
+                        continue;
+                    }
+
+                    let Some(event) = self.held.route(event, notes, |channel, note| {
+                        snapshot.new_note(channel, note)
+                    }) else {
+                        continue;
+                    };
+
+                    let channel = snapshot.output_channel(&event);
 
-                while let Some(event) = ctx.next_event() {
                     match event {
                         NoteEvent::Choke {
                             timing,
@@ -129,7 +490,7 @@ impl Plugin for ProgramChange {
                             note,
                             ..
                         } => {
-                            notes.set_off(note);
+                            notes.set_off(channel, note);
                             ctx.send_event(NoteEvent::Choke {
                                 timing,
                                 voice_id,
@@ -140,6 +501,28 @@ impl Plugin for ProgramChange {
                         NoteEvent::MidiCC {
                             timing, cc, value, ..
                         } => {
+                            if cc == DAMPER_PEDAL {
+                                if value >= 0.5 {
+                                    damper.set_on();
+                                } else {
+                                    damper.set_off();
+                                }
+
+                                // the pedal keeps acting on the notes ringing on other channels
+                                for held_channel in 0..16 {
+                                    if held_channel != channel
+                                        && !notes.is_channel_off(held_channel)
+                                    {
+                                        ctx.send_event(NoteEvent::MidiCC {
+                                            timing,
+                                            cc,
+                                            channel: held_channel,
+                                            value,
+                                        });
+                                    }
+                                }
+                            }
+
                             ctx.send_event(NoteEvent::MidiCC {
                                 timing,
                                 cc,
@@ -165,7 +548,15 @@ impl Plugin for ProgramChange {
                                 });
                             }
                         }
-                        NoteEvent::MidiProgramChange { .. } => {}
+                        NoteEvent::MidiProgramChange {
+                            timing, program, ..
+                        } => self.params.incoming_program.value().send(
+                            timing,
+                            channel,
+                            program,
+                            &self.params.program_map,
+                            ctx,
+                        ),
                         NoteEvent::NoteOff {
                             timing,
                             voice_id,
@@ -173,7 +564,7 @@ impl Plugin for ProgramChange {
                             velocity,
                             ..
                         } => {
-                            notes.set_off(note);
+                            notes.set_off(channel, note);
                             ctx.send_event(NoteEvent::NoteOff {
                                 timing,
                                 voice_id,
@@ -189,13 +580,13 @@ impl Plugin for ProgramChange {
                             velocity,
                             ..
                         } => {
-                            notes.set_on(note);
+                            notes.set_on(channel, note);
                             ctx.send_event(NoteEvent::NoteOn {
                                 timing,
                                 voice_id,
                                 channel,
                                 note,
-                                velocity,
+                                velocity: snapshot.velocity.apply(velocity),
                             });
                         }
                         NoteEvent::PolyBrightness {
@@ -320,18 +711,40 @@ impl Plugin for ProgramChange {
                     }
                 }
             }
-            InternalState::SeamlessSwitch { notes, snapshot } => {
-                let channel = snapshot.ch;
+            InternalState::SeamlessSwitch {
+                notes, snapshot, ..
+            } => {
+                while let Some(event) = next_input_event(input, pass_other, ctx) {
+                    if self.params.steps_on(&event) {
+                        continue;
+                    }
+
+                    let Some(event) = self.held.route(event, notes, |channel, note| {
+                        snapshot.new_note(channel, note)
+                    }) else {
+                        continue;
+                    };
+
+                    let channel = snapshot.output_channel(&event);
 
-                while let Some(event) = ctx.next_event() {
                     match event {
+                        event
+                            if self.params.forwards_on_hold(
+                                &event,
+                                channel,
+                                notes,
+                                snapshot.mpe.is_some(),
+                            ) =>
+                        {
+                            ctx.send_event(with_channel(event, channel))
+                        }
                         NoteEvent::Choke {
                             timing,
                             voice_id,
                             note,
                             ..
                         } => {
-                            notes.set_off(note);
+                            notes.set_off(channel, note);
                             ctx.send_event(NoteEvent::Choke {
                                 timing,
                                 voice_id,
@@ -346,7 +759,7 @@ impl Plugin for ProgramChange {
                             velocity,
                             ..
                         } => {
-                            notes.set_off(note);
+                            notes.set_off(channel, note);
                             ctx.send_event(NoteEvent::NoteOff {
                                 timing,
                                 voice_id,
@@ -361,59 +774,18 @@ impl Plugin for ProgramChange {
             }
         }
 
-        ProcessStatus::Normal
-    }
-}
-
-#[derive(Default)]
-struct NotesState(u128);
-
-impl NotesState {
-    fn is_all_off(&self) -> bool {
-        self.0 == 0
-    }
-
-    fn is_on(&self, note: u8) -> bool {
-        self.0 & note_mask(note) != 0
-    }
-
-    fn set_off(&mut self, note: u8) {
-        self.0 &= !note_mask(note);
-    }
-
-    fn set_on(&mut self, note: u8) {
-        self.0 |= note_mask(note);
-    }
-
-    fn send_all_note_off(&self, channel: u8, ctx: &mut impl ProcessContext<ProgramChange>) {
-        if self.0 != 0 {
-            for note in 0..127 {
-                if self.is_on(note) {
-                    // send midi message
-                    ctx.send_event(NoteEvent::NoteOff {
-                        timing: 0,
-                        voice_id: None,
-                        channel,
-                        note,
-                        velocity: 0.0,
-                    });
-                }
-            }
-        }
+        self.overlay.publish(self.state.view());
     }
 }
 
-#[inline]
-fn note_mask(note: u8) -> u128 {
-    1 << note
-}
-
 enum InternalState {
     On {
+        damper: DamperState,
         notes: NotesState,
         snapshot: ParamsSnapshot,
     },
     SeamlessSwitch {
+        damper: DamperState,
         notes: NotesState,
         snapshot: ParamsSnapshot,
     },
@@ -426,11 +798,87 @@ impl Default for InternalState {
     }
 }
 
+impl InternalState {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::On { .. } => "On",
+            Self::SeamlessSwitch { .. } => "Seamless switch",
+            Self::Off => "Off",
+        }
+    }
+
+    /// The state for the editor. The notes ringing on the other channels of a pool and all the
+    /// notes of a seamless switch are held through a switch.
+    fn view(&self) -> StateView<ParamsSnapshot> {
+        match *self {
+            Self::On {
+                damper,
+                notes,
+                snapshot,
+            } => {
+                let switched = match snapshot.mpe {
+                    // the member channels all play the patch
+                    Some(_) => NotesState::default(),
+                    None => {
+                        let mut switched = notes;
+                        switched.take_channel(snapshot.ch);
+                        switched
+                    }
+                };
+
+                StateView {
+                    state: self.name(),
+                    notes,
+                    switched,
+                    damper,
+                    snapshot: Some(snapshot),
+                }
+            }
+            Self::SeamlessSwitch {
+                damper,
+                notes,
+                snapshot,
+            } => StateView {
+                state: self.name(),
+                notes,
+                switched: notes,
+                damper,
+                snapshot: Some(snapshot),
+            },
+            Self::Off => StateView::off(),
+        }
+    }
+}
+
+/// The persisted fields a setup may set, see [`ProgramChangeParams`].
+const FIELDS: &[SetupField] = &[
+    SetupField::of::<Vec<SetlistEntry>>("setlist"),
+    SetupField::of::<usize>("setlist-position"),
+    SetupField::of::<ProgramMap>("program-map"),
+];
+
 #[derive(Params)]
 struct ProgramChangeParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+
+    /// The ordered snapshots of setlist mode.
+    #[persist = "setlist"]
+    setlist: RwLock<Vec<SetlistEntry>>,
+
+    #[persist = "setlist-position"]
+    setlist_position: AtomicUsize,
+
+    /// The translation table of the incoming program changes.
+    #[persist = "program-map"]
+    program_map: RwLock<ProgramMap>,
+
     #[id = "active"]
     active: BoolParam,
 
+    #[id = "add"]
+    add: BoolParam,
+
     #[id = "apb"]
     allow_pitch_bend: BoolParam,
 
@@ -446,91 +894,503 @@ struct ProgramChangeParams {
     #[id = "decay"]
     decay: IntParam,
 
+    #[id = "defer"]
+    defer: BoolParam,
+
+    #[id = "defert"]
+    defer_timeout: FloatParam,
+
     #[id = "expr"]
     expr: IntParam,
 
+    #[id = "expr14"]
+    expr_fine: IntParam,
+
+    #[id = "fade"]
+    fade: EnumParam<FadeController>,
+
+    #[id = "fadec"]
+    fade_curve: EnumParam<FadeCurve>,
+
+    #[id = "fader"]
+    fade_release: EnumParam<FadeRelease>,
+
+    #[id = "fadet"]
+    fade_time: FloatParam,
+
+    #[id = "hat"]
+    hold_aftertouch: EnumParam<HoldPolicy>,
+
+    #[id = "hcc"]
+    hold_controllers: EnumParam<HoldPolicy>,
+
+    #[id = "hpb"]
+    hold_pitch_bend: EnumParam<HoldPolicy>,
+
+    #[id = "hpoly"]
+    hold_poly: EnumParam<HoldPolicy>,
+
+    #[id = "in"]
+    input_channel: IntParam,
+
+    #[id = "inpass"]
+    pass_other: BoolParam,
+
+    #[id = "keyhi"]
+    key_high: IntParam,
+
+    #[id = "keylo"]
+    key_low: IntParam,
+
+    #[id = "keyout"]
+    key_outside: EnumParam<OutsideRange>,
+
     #[id = "lsb"]
     lsb: IntParam,
 
+    /// Sends the patch on the MPE manager channel, replacing the channel and the pool.
+    #[id = "mpe"]
+    mpe: BoolParam,
+
+    #[id = "mpem"]
+    mpe_members: IntParam,
+
     #[id = "msb"]
     msb: IntParam,
 
     #[id = "mw"]
     mw: IntParam,
 
+    #[id = "mw14"]
+    mw_fine: IntParam,
+
+    #[id = "next"]
+    next: BoolParam,
+
+    #[id = "nextcc"]
+    next_cc: IntParam,
+
+    #[id = "nrpn"]
+    nrpn: IntParam,
+
+    #[id = "oct"]
+    octave: IntParam,
+
+    #[id = "nrpnv"]
+    nrpn_value: IntParam,
+
+    #[id = "pbr"]
+    pitch_bend_range: IntParam,
+
+    #[id = "pbrc"]
+    pitch_bend_range_cents: IntParam,
+
     #[id = "pc"]
     pc: IntParam,
 
+    #[id = "pcin"]
+    incoming_program: EnumParam<ProgramChangePolicy>,
+
+    #[id = "pool"]
+    pool: IntParam,
+
+    #[id = "prev"]
+    previous: BoolParam,
+
+    #[id = "prevcc"]
+    previous_cc: IntParam,
+
     #[id = "release"]
     release: IntParam,
 
     #[id = "resonance"]
     resonance: IntParam,
 
+    #[id = "setlist"]
+    setlist_mode: BoolParam,
+
+    #[id = "store"]
+    store: BoolParam,
+
+    #[id = "transp"]
+    transpose: IntParam,
+
+    #[id = "velc"]
+    velocity_curve: EnumParam<VelocityCurve>,
+
+    #[id = "velf"]
+    velocity_fixed: IntParam,
+
+    #[id = "velmax"]
+    velocity_max: IntParam,
+
+    #[id = "velmin"]
+    velocity_min: IntParam,
+
     #[id = "vol"]
     vol: IntParam,
+
+    #[id = "vol14"]
+    vol_fine: IntParam,
 }
 
 impl Default for ProgramChangeParams {
     fn default() -> Self {
         Self {
+            editor_state: editor::default_state(),
+            setlist: RwLock::new(Vec::new()),
+            setlist_position: AtomicUsize::new(0),
+            program_map: Default::default(),
             active: BoolParam::new("Active", true),
+            add: BoolParam::new("Add Setlist Step", false),
             allow_pitch_bend: BoolParam::new("Allow Pitch Bend", true),
             attack: IntParam::new("Attack", 64, IntRange::Linear { min: 0, max: 127 }),
             ch: IntParam::new("Channel", 1, IntRange::Linear { min: 1, max: 16 }),
             cutoff: IntParam::new("Cutoff", 64, IntRange::Linear { min: 0, max: 127 }),
             decay: IntParam::new("Decay", 64, IntRange::Linear { min: 0, max: 127 }),
+            defer: BoolParam::new("Defer Until Silence", false),
+            defer_timeout: FloatParam::new(
+                "Defer Timeout",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 30000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
             expr: IntParam::new("Expresion", 127, IntRange::Linear { min: 0, max: 127 }),
+            expr_fine: fine_param("Expression (14-bit)"),
+            fade: EnumParam::new("Crossfade", FadeController::Off),
+            fade_curve: EnumParam::new("Crossfade Curve", FadeCurve::Linear),
+            fade_release: EnumParam::new("Crossfade Release", FadeRelease::KeysUp),
+            fade_time: FloatParam::new(
+                "Crossfade Time",
+                250.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 10000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(1.0),
+            hold_aftertouch: EnumParam::new("Hold Aftertouch", HoldPolicy::Drop),
+            hold_controllers: EnumParam::new("Hold Controllers", HoldPolicy::Drop),
+            hold_pitch_bend: EnumParam::new("Hold Pitch Bend", HoldPolicy::Drop),
+            hold_poly: EnumParam::new("Hold Poly Expression", HoldPolicy::Drop),
+            input_channel: input_channel_param(),
+            pass_other: BoolParam::new("Pass Other Channels", false),
+            key_high: key_param("Key Range High", 127),
+            key_low: key_param("Key Range Low", 0),
+            key_outside: EnumParam::new("Outside Key Range", OutsideRange::Drop),
             lsb: IntParam::new("Bank Select LSB", 0, IntRange::Linear { min: 0, max: 127 }),
+            mpe: BoolParam::new("MPE", false),
+            mpe_members: IntParam::new(
+                "MPE Member Channels",
+                15,
+                IntRange::Linear { min: 1, max: 15 },
+            ),
             msb: IntParam::new("Bank Select MSB", 0, IntRange::Linear { min: 0, max: 127 }),
             mw: IntParam::new("Mod Wheel", 0, IntRange::Linear { min: 0, max: 127 }),
+            mw_fine: fine_param("Mod Wheel (14-bit)"),
+            next: BoolParam::new("Next Step", false),
+            next_cc: trigger_cc_param("Next Step CC"),
+            nrpn: IntParam::new(
+                "NRPN Number",
+                -1,
+                IntRange::Linear {
+                    min: -1,
+                    max: MAX_14_BIT as i32,
+                },
+            ),
+            nrpn_value: IntParam::new(
+                "NRPN Value",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_14_BIT as i32,
+                },
+            ),
+            octave: IntParam::new("Octave", 0, IntRange::Linear { min: -4, max: 4 }),
+            pitch_bend_range: IntParam::new(
+                "Pitch Bend Range",
+                -1,
+                IntRange::Linear { min: -1, max: 48 },
+            )
+            .with_unit(" st"),
+            pitch_bend_range_cents: IntParam::new(
+                "Pitch Bend Range Cents",
+                0,
+                IntRange::Linear { min: 0, max: 99 },
+            )
+            .with_unit(" ct"),
             pc: IntParam::new("Program Change", 0, IntRange::Linear { min: 0, max: 127 }),
+            incoming_program: EnumParam::new("Incoming Program Change", ProgramChangePolicy::Drop),
+            pool: IntParam::new("Channel Pool", 1, IntRange::Linear { min: 1, max: 16 }),
+            previous: BoolParam::new("Previous Step", false),
+            previous_cc: trigger_cc_param("Previous Step CC"),
             release: IntParam::new("Release", 64, IntRange::Linear { min: 0, max: 127 }),
             resonance: IntParam::new("Resonance", 64, IntRange::Linear { min: 0, max: 127 }),
+            setlist_mode: BoolParam::new("Setlist", false),
+            store: BoolParam::new("Store Setlist Step", false),
+            transpose: IntParam::new("Transpose", 0, IntRange::Linear { min: -24, max: 24 })
+                .with_unit(" st"),
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::Linear),
+            velocity_fixed: velocity_param("Fixed Velocity", 100),
+            velocity_max: velocity_param("Velocity Max", 127),
+            velocity_min: velocity_param("Velocity Min", 1),
             vol: IntParam::new("Volume", 100, IntRange::Linear { min: 0, max: 127 }),
+            vol_fine: fine_param("Volume (14-bit)"),
         }
     }
 }
 
+fn key_param(name: &str, default: i32) -> IntParam {
+    IntParam::new(name, default, IntRange::Linear { min: 0, max: 127 })
+        .with_value_to_string(formatters::v2s_i32_note_formatter())
+        .with_string_to_value(formatters::s2v_i32_note_formatter())
+}
+
+/// A footswitch controller stepping through the setlist, `-1` disables it.
+fn trigger_cc_param(name: &str) -> IntParam {
+    IntParam::new(name, -1, IntRange::Linear { min: -1, max: 127 })
+}
+
+impl WithProgramMap for ProgramChangeParams {
+    fn program_map(&self) -> &RwLock<ProgramMap> {
+        &self.program_map
+    }
+}
+
 impl ProgramChangeParams {
     fn channel(&self) -> u8 {
         self.ch.value().clamp(1, 16) as u8 - 1
     }
 
+    /// The input channel, `None` in omni mode.
+    fn input_channel(&self) -> Option<u8> {
+        input_channel(&self.input_channel)
+    }
+
+    fn pool(&self) -> u8 {
+        self.pool.value().clamp(1, 16) as u8
+    }
+
+    /// Whether the event is forwarded while notes are held, events disabled by the allow
+    /// parameters never are.
+    fn forwards_on_hold(
+        &self,
+        event: &NoteEvent<()>,
+        channel: u8,
+        notes: &NotesState,
+        mpe: bool,
+    ) -> bool {
+        let allowed =
+            !matches!(event, NoteEvent::MidiPitchBend { .. }) || self.allow_pitch_bend.value();
+
+        allowed && self.hold_policies().forwards(event, channel, notes, mpe)
+    }
+
+    fn hold_policies(&self) -> HoldPolicies {
+        HoldPolicies {
+            pitch_bend: self.hold_pitch_bend.value(),
+            aftertouch: self.hold_aftertouch.value(),
+            controllers: self.hold_controllers.value(),
+            poly: self.hold_poly.value(),
+        }
+    }
+
+    /// RPN 0,0 for the patch, `None` leaves the synth's range untouched.
+    fn pitch_bend_range(&self) -> Option<ParameterChange> {
+        let semitones = self.pitch_bend_range.value();
+
+        if semitones < 0 {
+            None
+        } else {
+            let cents = self.pitch_bend_range_cents.value().clamp(0, 99);
+            Some(ParameterChange::pitch_bend_range(semitones as u8, cents as u8).terminated())
+        }
+    }
+
+    /// Moves through the setlist, ignored while the setlist is being edited.
+    fn step(&self, step: Step) {
+        if let Ok(entries) = self.setlist.try_read() {
+            let position = self.setlist_position.load(Ordering::Relaxed);
+
+            if let Some(position) = step.apply(position, entries.len()) {
+                self.setlist_position.store(position, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Steps through the setlist on its MIDI triggers, returning whether the event was one.
+    /// The step is played from the next block on.
+    fn steps_on(&self, event: &NoteEvent<()>) -> bool {
+        if !self.setlist_mode.value() {
+            return false;
+        }
+
+        match *event {
+            NoteEvent::MidiCC { cc, value, .. } if cc as i32 == self.next_cc.value() => {
+                if value >= 0.5 {
+                    self.step(Step::Next);
+                }
+                true
+            }
+            NoteEvent::MidiCC { cc, value, .. } if cc as i32 == self.previous_cc.value() => {
+                if value >= 0.5 {
+                    self.step(Step::Previous);
+                }
+                true
+            }
+            NoteEvent::MidiProgramChange { program, .. }
+                if self.incoming_program.value() == ProgramChangePolicy::SelectStep =>
+            {
+                self.step(Step::Select(program as usize));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn key_range(&self) -> KeyRange {
+        KeyRange {
+            low: self.key_low.value().clamp(0, 127) as u8,
+            high: self.key_high.value().clamp(0, 127) as u8,
+            outside: self.key_outside.value(),
+        }
+    }
+
     fn snapshot(&self) -> ParamsSnapshot {
+        let mpe = mpe_members(&self.mpe, &self.mpe_members);
+        // in MPE mode the whole patch goes to the manager channel of the zone, without a pool
+        let (ch, pool) = match mpe {
+            Some(_) => (MPE_MANAGER_CHANNEL, 1),
+            None => (self.channel(), self.pool()),
+        };
+        // the coarse slots of a patch start at 0, they always have a value
+        let controller = |coarse: &IntParam, fine: &IntParam| {
+            controller_value(coarse, fine).unwrap_or(ControllerValue::Coarse(0))
+        };
+
         ParamsSnapshot {
             attack: self.attack.value().clamp(0, 127) as u8,
-            ch: self.channel(),
+            base: ch,
+            ch,
             cutoff: self.cutoff.value().clamp(0, 127) as u8,
             decay: self.decay.value().clamp(0, 127) as u8,
-            expr: self.expr.value().clamp(0, 127) as u8,
+            expr: controller(&self.expr, &self.expr_fine),
+            fade: self.fade.value().cc(),
+            key_range: self.key_range(),
             lsb: self.lsb.value().clamp(0, 127) as u8,
+            mpe,
             msb: self.msb.value().clamp(0, 127) as u8,
-            mw: self.mw.value().clamp(0, 127) as u8,
+            mw: controller(&self.mw, &self.mw_fine),
+            nrpn: nrpn_param_change(&self.nrpn, &self.nrpn_value),
+            pitch_bend_range: self.pitch_bend_range(),
+            pool,
             pc: self.pc.value().clamp(0, 127) as u8,
             release: self.release.value().clamp(0, 127) as u8,
             resonance: self.resonance.value().clamp(0, 127) as u8,
-            vol: self.vol.value().clamp(0, 127) as u8,
+            transpose: transpose_semitones(&self.transpose, &self.octave),
+            velocity: velocity_transform(
+                &self.velocity_curve,
+                &self.velocity_fixed,
+                &self.velocity_min,
+                &self.velocity_max,
+            ),
+            vol: controller(&self.vol, &self.vol_fine),
         }
     }
 }
 
+/// The fields missing from a snapshot saved by an older version take their default value.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
 struct ParamsSnapshot {
     attack: u8,
+    /// First channel of the pool, `ch` is the channel the patch is sent on.
+    base: u8,
     ch: u8,
     cutoff: u8,
     decay: u8,
-    expr: u8,
+    expr: ControllerValue,
+    fade: Option<u8>,
+    key_range: KeyRange,
     lsb: u8,
+    mpe: Option<u8>,
     msb: u8,
-    mw: u8,
+    mw: ControllerValue,
+    nrpn: Option<ParameterChange>,
+    pitch_bend_range: Option<ParameterChange>,
+    pool: u8,
     pc: u8,
     release: u8,
     resonance: u8,
-    vol: u8,
+    /// Semitones added to the notes played on the patch.
+    transpose: i8,
+    /// Applied to the note ons played on the patch, note offs keep their release velocity.
+    velocity: VelocityTransform,
+    vol: ControllerValue,
+}
+
+impl Default for ParamsSnapshot {
+    fn default() -> Self {
+        ProgramChangeParams::default().snapshot()
+    }
 }
 
 impl ParamsSnapshot {
+    fn changes_program(&self, old: &ParamsSnapshot) -> bool {
+        old.msb != self.msb || old.lsb != self.lsb || old.pc != self.pc
+    }
+
+    /// The crossfaded controller and its level, the ramps drive it instead of [`Self::send`].
+    fn faded_level(&self) -> Option<(u8, u8)> {
+        self.fade.map(|cc| (cc, self.level_of(Some(cc))))
+    }
+
+    /// The 7-bit level of the volume or expression controller `cc`.
+    fn level_of(&self, cc: Option<u8>) -> u8 {
+        match cc {
+            Some(MAIN_VOLUME_MSB) => self.vol.coarse(),
+            Some(EXPRESSION_CONTROLLER_MSB) => self.expr.coarse(),
+            _ => 0,
+        }
+    }
+
+    /// Per-note events are already routed by the [`NoteMap`], channel events keep their member
+    /// channel in MPE mode and go to the patch channel otherwise.
+    fn output_channel(&self, event: &NoteEvent<()>) -> u8 {
+        match (self.mpe, event_note(event)) {
+            (None, None) => self.ch,
+            _ => event.channel().unwrap_or(self.ch),
+        }
+    }
+
+    /// The output channel and note of a new note: its member channel in MPE mode, the patch
+    /// channel otherwise, limited to the key range then transposed. Notes pushed out of the MIDI
+    /// range are dropped.
+    /// Only new notes come here, the [`NoteMap`] routes the events of a held note to where it was
+    /// pressed.
+    fn new_note(&self, channel: u8, note: u8) -> Option<(u8, u8)> {
+        let channel = if self.mpe.is_some() { channel } else { self.ch };
+        let note = self.key_range.apply(note)? as i16 + self.transpose as i16;
+
+        (0..128).contains(&note).then_some((channel, note as u8))
+    }
+
+    /// The channels of the pool, starting with the base channel.
+    fn pool_channels(&self) -> impl Iterator<Item = u8> {
+        let base = self.base;
+        (0..self.pool).map(move |offset| (base + offset) % 16)
+    }
+
+    fn same_pool(&self, other: &ParamsSnapshot) -> bool {
+        self.base == other.base && self.pool == other.pool
+    }
+
     fn create_cc(&self, timing: u32, cc: u8, value: u8) -> NoteEvent<()> {
         NoteEvent::MidiCC {
             timing,
@@ -548,13 +1408,34 @@ impl ParamsSnapshot {
         }
     }
 
+    fn send_controller(
+        &self,
+        timing: u32,
+        cc: u8,
+        value: ControllerValue,
+        context: &mut impl ProcessContext<ProgramChange>,
+    ) {
+        for cc in value.messages(self.ch, cc) {
+            context.send_event(cc.to_event(timing));
+        }
+    }
+
+    fn send_parameter(
+        &self,
+        timing: u32,
+        change: ParameterChange,
+        context: &mut impl ProcessContext<ProgramChange>,
+    ) {
+        for cc in change.messages(self.ch) {
+            context.send_event(cc.to_event(timing));
+        }
+    }
+
     fn send(&self, old: Option<&ParamsSnapshot>, context: &mut impl ProcessContext<ProgramChange>) {
         let old = old.filter(|old| old.ch == self.ch);
 
         // we must handle bank select with program change
-        if old.map_or(true, |old| {
-            old.msb != self.msb || old.lsb != self.lsb || old.pc != self.pc
-        }) {
+        if old.map_or(true, |old| self.changes_program(old)) {
             context.send_event(self.create_cc(0, BANK_SELECT_MSB, self.msb));
             context.send_event(self.create_cc(0, BANK_SELECT_LSB, self.lsb));
             context.send_event(self.create_pc());
@@ -572,12 +1453,33 @@ impl ParamsSnapshot {
             context.send_event(self.create_cc(2, GENERAL_PURPOSE_CONTROLLER_5_MSB, self.decay));
         }
 
-        if old.map_or(true, |old| old.expr != self.expr) {
-            context.send_event(self.create_cc(2, EXPRESSION_CONTROLLER_MSB, self.expr));
+        if self.fade != Some(EXPRESSION_CONTROLLER_MSB)
+            && old.map_or(true, |old| old.expr != self.expr)
+        {
+            self.send_controller(2, EXPRESSION_CONTROLLER_MSB, self.expr, context);
+        }
+
+        if let Some(members) = self.mpe {
+            if old.map_or(true, |old| old.mpe != self.mpe) {
+                let change = ParameterChange::mpe_configuration(members).terminated();
+                self.send_parameter(2, change, context);
+            }
         }
 
         if old.map_or(true, |old| old.mw != self.mw) {
-            context.send_event(self.create_cc(2, MODULATION_MSB, self.mw));
+            self.send_controller(2, MODULATION_MSB, self.mw, context);
+        }
+
+        if let Some(nrpn) = self.nrpn {
+            if old.map_or(true, |old| old.nrpn != self.nrpn) {
+                self.send_parameter(2, nrpn, context);
+            }
+        }
+
+        if let Some(pitch_bend_range) = self.pitch_bend_range {
+            if old.map_or(true, |old| old.pitch_bend_range != self.pitch_bend_range) {
+                self.send_parameter(2, pitch_bend_range, context);
+            }
         }
 
         if old.map_or(true, |old| old.release != self.release) {
@@ -588,8 +1490,8 @@ impl ParamsSnapshot {
             context.send_event(self.create_cc(2, SOUND_CONTROLLER_2, self.resonance));
         }
 
-        if old.map_or(true, |old| old.vol != self.vol) {
-            context.send_event(self.create_cc(2, MAIN_VOLUME_MSB, self.vol));
+        if self.fade != Some(MAIN_VOLUME_MSB) && old.map_or(true, |old| old.vol != self.vol) {
+            self.send_controller(2, MAIN_VOLUME_MSB, self.vol, context);
         }
 
         if old.is_none() {
@@ -624,3 +1526,336 @@ impl Vst3Plugin for ProgramChange {
 
 nih_export_clap!(ProgramChange);
 nih_export_vst3!(ProgramChange);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// A host feeding the plugin its input events and keeping the events it sends.
+    #[derive(Default)]
+    pub(crate) struct TestContext {
+        pub input: VecDeque<NoteEvent<()>>,
+        pub output: Vec<NoteEvent<()>>,
+    }
+
+    impl ProcessContext<ProgramChange> for TestContext {
+        fn plugin_api(&self) -> PluginApi {
+            PluginApi::Clap
+        }
+
+        fn execute_background(&self, _task: SetlistTask) {}
+
+        fn execute_gui(&self, _task: SetlistTask) {}
+
+        fn transport(&self) -> &Transport {
+            unimplemented!("the plugin does not read the transport")
+        }
+
+        fn next_event(&mut self) -> Option<NoteEvent<()>> {
+            self.input.pop_front()
+        }
+
+        fn send_event(&mut self, event: NoteEvent<()>) {
+            self.output.push(event);
+        }
+
+        fn set_latency_samples(&self, _samples: u32) {}
+
+        fn set_current_voice_capacity(&self, _capacity: u32) {}
+    }
+
+    const BLOCK: u32 = 256;
+
+    impl ProgramChange {
+        fn with_params(params: ProgramChangeParams) -> Self {
+            Self {
+                params: Arc::new(params),
+                sample_rate: 48_000.0,
+                ..Default::default()
+            }
+        }
+
+        /// Runs a block with the `input` events, returning the events sent.
+        fn run(&mut self, input: impl IntoIterator<Item = NoteEvent<()>>) -> Vec<NoteEvent<()>> {
+            let mut ctx = TestContext {
+                input: input.into_iter().collect(),
+                output: Vec::new(),
+            };
+
+            self.process_block(BLOCK, &mut ctx);
+            ctx.output
+        }
+
+        /// Runs `count` blocks without input.
+        fn idle(&mut self, count: usize) -> Vec<NoteEvent<()>> {
+            (0..count).flat_map(|_| self.run([])).collect()
+        }
+
+        /// Plays the next blocks with `params`, like a host automating them.
+        fn set(&mut self, params: ProgramChangeParams) {
+            self.params = Arc::new(params);
+        }
+    }
+
+    fn int(value: i32) -> IntParam {
+        IntParam::new("Test", value, IntRange::Linear { min: -1, max: 127 })
+    }
+
+    fn toggle(value: bool) -> BoolParam {
+        BoolParam::new("Test", value)
+    }
+
+    fn ms(value: f32) -> FloatParam {
+        FloatParam::new(
+            "Test",
+            value,
+            FloatRange::Linear {
+                min: 0.0,
+                max: 30000.0,
+            },
+        )
+    }
+
+    fn note_on(channel: u8, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 0.5,
+        }
+    }
+
+    fn note_off(channel: u8, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 0.5,
+        }
+    }
+
+    fn cc(channel: u8, cc: u8, value: u8) -> NoteEvent<()> {
+        NoteEvent::MidiCC {
+            timing: 0,
+            channel,
+            cc,
+            value: value as f32 / 127.0,
+        }
+    }
+
+    /// The values of the controller `number` sent on `channel`.
+    fn ccs(events: &[NoteEvent<()>], channel: u8, number: u8) -> Vec<u8> {
+        events
+            .iter()
+            .filter_map(|event| match *event {
+                NoteEvent::MidiCC {
+                    channel: c,
+                    cc,
+                    value,
+                    ..
+                } if c == channel && cc == number => Some((value * 127.0).round() as u8),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The channel and note of the note offs sent.
+    fn note_offs(events: &[NoteEvent<()>]) -> Vec<(u8, u8)> {
+        events
+            .iter()
+            .filter_map(|event| match *event {
+                NoteEvent::NoteOff { channel, note, .. } => Some((channel, note)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The channel and program of the program changes sent.
+    fn programs(events: &[NoteEvent<()>]) -> Vec<(u8, u8)> {
+        events
+            .iter()
+            .filter_map(|event| match *event {
+                NoteEvent::MidiProgramChange {
+                    channel, program, ..
+                } => Some((channel, program)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fade_out_runs_on_while_the_next_pool_channel_fades_in() {
+        let crossfade = |active| ProgramChangeParams {
+            active: toggle(active),
+            pool: int(2),
+            fade: EnumParam::new("Crossfade", FadeController::Volume),
+            fade_release: EnumParam::new("Crossfade Release", FadeRelease::FadeEnd),
+            fade_time: ms(20.0),
+            ..Default::default()
+        };
+
+        let mut plugin = ProgramChange::with_params(crossfade(true));
+        let sent = plugin.run([note_on(0, 60)]);
+        assert!(sent.contains(&note_on(1, 60)));
+        assert_eq!(ccs(&plugin.idle(5), 1, MAIN_VOLUME_MSB).last(), Some(&100));
+
+        // the held note fades out on channel 2 while the patch fades in on channel 1
+        plugin.set(crossfade(false));
+        let mut sent = plugin.run([]);
+        plugin.set(crossfade(true));
+        sent.extend(plugin.idle(6));
+
+        let faded_out = ccs(&sent, 1, MAIN_VOLUME_MSB);
+        assert_eq!(faded_out.first(), Some(&100));
+        assert_eq!(faded_out.last(), Some(&0));
+        assert_eq!(ccs(&sent, 0, MAIN_VOLUME_MSB).last(), Some(&100));
+
+        // the voices of the faded channel are released at the end of the fade
+        assert_eq!(note_offs(&sent), [(1, 60)]);
+        assert!(matches!(plugin.state, InternalState::On { .. }));
+
+        // the note off of the key comes too late and is not sent again
+        assert_eq!(note_offs(&plugin.run([note_off(0, 60)])), []);
+    }
+
+    fn deferred_patch(program: i32, timeout: f32) -> ProgramChangeParams {
+        ProgramChangeParams {
+            defer: toggle(true),
+            defer_timeout: ms(timeout),
+            pc: int(program),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn deferred_program_change_waits_for_silence() {
+        let mut plugin = ProgramChange::with_params(deferred_patch(0, 0.0));
+        assert_eq!(programs(&plugin.run([note_on(0, 60)])), [(0, 0)]);
+
+        // the notes played meanwhile keep the current patch
+        plugin.set(deferred_patch(5, 0.0));
+        let sent = plugin.run([note_on(0, 62)]);
+        assert_eq!(programs(&sent), []);
+        assert!(sent.contains(&note_on(0, 62)));
+
+        // the damper keeps the notes ringing once the keys are up
+        let sent = plugin.run([cc(0, DAMPER_PEDAL, 127), note_off(0, 60), note_off(0, 62)]);
+        assert_eq!(programs(&sent), []);
+        assert_eq!(programs(&plugin.idle(4)), []);
+
+        // silence is seen from the block after the pedal is released
+        assert_eq!(programs(&plugin.run([cc(0, DAMPER_PEDAL, 0)])), []);
+        assert_eq!(programs(&plugin.run([])), [(0, 5)]);
+    }
+
+    #[test]
+    fn deferred_program_change_fires_after_the_timeout() {
+        let mut plugin = ProgramChange::with_params(deferred_patch(0, 10.0));
+        plugin.run([note_on(0, 60)]);
+
+        // 10 ms are 480 samples, the third block starts past them
+        plugin.set(deferred_patch(5, 10.0));
+        assert_eq!(programs(&plugin.idle(2)), []);
+        assert_eq!(programs(&plugin.run([])), [(0, 5)]);
+    }
+
+    #[test]
+    fn zero_defer_timeout_waits_forever() {
+        let mut plugin = ProgramChange::with_params(deferred_patch(0, 0.0));
+        plugin.run([note_on(0, 60)]);
+
+        plugin.set(deferred_patch(5, 0.0));
+        assert_eq!(programs(&plugin.idle(400)), []);
+        assert_eq!(programs(&plugin.run([note_off(0, 60)])), []);
+        assert_eq!(programs(&plugin.run([])), [(0, 5)]);
+    }
+
+    #[test]
+    fn other_changes_are_not_deferred() {
+        let mut plugin = ProgramChange::with_params(deferred_patch(0, 0.0));
+        plugin.run([note_on(0, 60)]);
+
+        plugin.set(ProgramChangeParams {
+            mw: int(90),
+            ..deferred_patch(0, 0.0)
+        });
+        let sent = plugin.run([]);
+        assert_eq!(ccs(&sent, 0, MODULATION_MSB), [90]);
+        assert_eq!(programs(&sent), []);
+    }
+
+    #[test]
+    fn footswitch_controllers_step_through_the_setlist() {
+        let entry = |pc| SetlistEntry {
+            name: String::new(),
+            snapshot: ParamsSnapshot {
+                pc,
+                ..Default::default()
+            },
+        };
+        let mut plugin = ProgramChange::with_params(ProgramChangeParams {
+            setlist: RwLock::new(vec![entry(1), entry(2), entry(3)]),
+            setlist_mode: toggle(true),
+            next_cc: int(80),
+            previous_cc: int(81),
+            ..Default::default()
+        });
+        assert_eq!(programs(&plugin.run([])), [(0, 1)]);
+
+        // the footswitches are not forwarded, releasing them does not step
+        let sent = plugin.run([cc(0, 80, 127), cc(0, 80, 0)]);
+        assert_eq!(ccs(&sent, 0, 80), []);
+        assert_eq!(programs(&plugin.run([])), [(0, 2)]);
+
+        plugin.run([cc(0, 80, 127)]);
+        assert_eq!(programs(&plugin.run([cc(0, 80, 127)])), [(0, 3)]);
+        assert_eq!(programs(&plugin.run([])), []);
+        assert_eq!(plugin.params.setlist_position.load(Ordering::Relaxed), 2);
+
+        plugin.run([cc(0, 81, 127)]);
+        assert_eq!(programs(&plugin.run([])), [(0, 2)]);
+    }
+
+    fn filtered(pass_other: bool) -> ProgramChange {
+        let mut plugin = ProgramChange::with_params(ProgramChangeParams {
+            ch: int(3),
+            input_channel: int(2),
+            pass_other: toggle(pass_other),
+            ..Default::default()
+        });
+
+        plugin.run([]);
+        plugin
+    }
+
+    #[test]
+    fn other_channels_pass_through_unchanged() {
+        let mut plugin = filtered(true);
+        let other = [note_on(0, 60), cc(5, MODULATION_MSB, 20), note_off(0, 60)];
+
+        assert_eq!(plugin.run(other), other);
+        assert!(matches!(&plugin.state, InternalState::On { notes, .. } if notes.is_all_off()));
+    }
+
+    #[test]
+    fn other_channels_are_dropped_without_pass_through() {
+        let mut plugin = filtered(false);
+
+        assert_eq!(plugin.run([note_on(0, 60), cc(5, MODULATION_MSB, 20)]), []);
+        assert!(matches!(&plugin.state, InternalState::On { notes, .. } if notes.is_all_off()));
+    }
+
+    #[test]
+    fn only_the_input_channel_reaches_the_patch() {
+        for pass_other in [false, true] {
+            let mut plugin = filtered(pass_other);
+
+            assert_eq!(plugin.run([note_on(1, 62)]), [note_on(2, 62)]);
+            assert!(matches!(&plugin.state, InternalState::On { notes, .. } if notes.is_on(2, 62)));
+            assert_eq!(plugin.run([note_off(1, 62)]), [note_off(2, 62)]);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+use nih_plug::prelude::*;
+use program_change::ProgramChange;
+
+fn main() {
+    nih_export_standalone::<ProgramChange>();
+}
@@ -0,0 +1,28 @@
+/// The factory presets, stepped through in the editor.
+pub const FACTORY_PRESETS: &[&str] = &[
+    include_str!("../presets/gm-standard-kit.json"),
+    include_str!("../presets/gm-room-kit.json"),
+    include_str!("../presets/gm-power-kit.json"),
+    include_str!("../presets/gm-electronic-kit.json"),
+    include_str!("../presets/gm-tr-808-kit.json"),
+    include_str!("../presets/gm-jazz-kit.json"),
+    include_str!("../presets/gm-brush-kit.json"),
+    include_str!("../presets/gm-orchestra-kit.json"),
+    include_str!("../presets/gm-piano-crossfade.json"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProgramChangeParams;
+    use midi_common::check_presets;
+
+    #[test]
+    fn factory_presets_are_valid() {
+        let presets = check_presets(FACTORY_PRESETS, &ProgramChangeParams::default()).unwrap();
+        let files = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/presets")).unwrap();
+
+        // every file of the directory is listed
+        assert_eq!(presets.len(), files.count());
+    }
+}
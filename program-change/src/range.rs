@@ -0,0 +1,104 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What happens to the keys played outside the key range.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutsideRange {
+    #[name = "Drop"]
+    Drop,
+
+    /// Moved by octaves into the range, dropped when no octave of the key fits.
+    #[name = "Fold"]
+    Fold,
+}
+
+/// The keys a patch responds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRange {
+    pub low: u8,
+    pub high: u8,
+    pub outside: OutsideRange,
+}
+
+impl KeyRange {
+    /// The key the patch plays for `note`, `None` when it does not respond to it.
+    pub fn apply(self, note: u8) -> Option<u8> {
+        let (low, high) = (self.low.min(self.high), self.high.max(self.low));
+
+        if (low..=high).contains(&note) {
+            return Some(note);
+        }
+
+        match self.outside {
+            OutsideRange::Drop => None,
+            OutsideRange::Fold => {
+                let mut note = note;
+
+                while note < low {
+                    note += 12;
+                }
+
+                while note > high {
+                    note = note.checked_sub(12)?;
+                }
+
+                (note >= low).then_some(note)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(low: u8, high: u8, outside: OutsideRange) -> KeyRange {
+        KeyRange { low, high, outside }
+    }
+
+    #[test]
+    fn bounds_are_in_the_range() {
+        let drop = range(36, 48, OutsideRange::Drop);
+
+        assert_eq!(drop.apply(36), Some(36));
+        assert_eq!(drop.apply(48), Some(48));
+        assert_eq!(drop.apply(35), None);
+        assert_eq!(drop.apply(49), None);
+    }
+
+    #[test]
+    fn fold_moves_by_octaves_into_the_range() {
+        let fold = range(36, 48, OutsideRange::Fold);
+
+        assert_eq!(fold.apply(24), Some(36));
+        assert_eq!(fold.apply(35), Some(47));
+        assert_eq!(fold.apply(49), Some(37));
+        assert_eq!(fold.apply(60), Some(48));
+        assert_eq!(fold.apply(0), Some(36));
+        assert_eq!(fold.apply(127), Some(43));
+
+        assert_eq!(range(0, 11, OutsideRange::Fold).apply(127), Some(7));
+        assert_eq!(range(116, 127, OutsideRange::Fold).apply(0), Some(120));
+    }
+
+    #[test]
+    fn fold_drops_keys_without_an_octave_in_a_narrow_range() {
+        let fold = range(60, 64, OutsideRange::Fold);
+
+        assert_eq!(fold.apply(50), Some(62));
+        assert_eq!(fold.apply(72), Some(60));
+        assert_eq!(fold.apply(55), None);
+        assert_eq!(fold.apply(66), None);
+        assert_eq!(range(0, 5, OutsideRange::Fold).apply(127), None);
+    }
+
+    #[test]
+    fn swapped_bounds_are_the_same_range() {
+        let swapped = range(48, 36, OutsideRange::Fold);
+
+        assert_eq!(swapped.apply(40), Some(40));
+        assert_eq!(swapped.apply(24), Some(36));
+        assert_eq!(swapped.apply(60), Some(48));
+        assert_eq!(range(48, 36, OutsideRange::Drop).apply(50), None);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::ParamsSnapshot;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    RwLock,
+};
+
+/// A named snapshot of the setlist.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SetlistEntry {
+    pub name: String,
+    pub snapshot: ParamsSnapshot,
+}
+
+/// A move through the setlist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Next,
+    Previous,
+    Select(usize),
+}
+
+impl Step {
+    /// The position after the step, `None` when it leaves the setlist.
+    pub fn apply(self, position: usize, len: usize) -> Option<usize> {
+        let last = len.checked_sub(1)?;
+
+        match self {
+            Self::Next => Some((position + 1).min(last)),
+            Self::Previous => Some(position.saturating_sub(1).min(last)),
+            Self::Select(index) => (index <= last).then_some(index),
+        }
+    }
+}
+
+/// Edits of the setlist, they run on a background thread so the audio thread never allocates.
+pub enum SetlistTask {
+    /// Replaces the entry at `position`, appending past the end of the setlist.
+    Store {
+        position: usize,
+        snapshot: ParamsSnapshot,
+    },
+
+    /// Inserts an entry after `position` and moves to it.
+    Insert {
+        position: usize,
+        snapshot: ParamsSnapshot,
+    },
+}
+
+impl SetlistTask {
+    pub fn run(self, entries: &RwLock<Vec<SetlistEntry>>, current: &AtomicUsize) {
+        // a panic while the lock was held leaves the setlist as it is, like the readers do
+        let Ok(mut entries) = entries.write() else {
+            return;
+        };
+
+        match self {
+            Self::Store { position, snapshot } => match entries.get_mut(position) {
+                Some(entry) => entry.snapshot = snapshot,
+                None => {
+                    let name = entry_name(entries.len());
+                    entries.push(SetlistEntry { name, snapshot });
+                    current.store(entries.len() - 1, Ordering::Relaxed);
+                }
+            },
+            Self::Insert { position, snapshot } => {
+                let position = (position + 1).min(entries.len());
+                let name = entry_name(position);
+
+                entries.insert(position, SetlistEntry { name, snapshot });
+                current.store(position, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+fn entry_name(index: usize) -> String {
+    format!("Step {}", index + 1)
+}
+
+/// Rising edge detection for the trigger parameters.
+#[derive(Default)]
+pub struct Trigger(bool);
+
+impl Trigger {
+    pub fn rises(&mut self, value: bool) -> bool {
+        let rises = value && !self.0;
+        self.0 = value;
+        rises
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(programs: &[u8]) -> RwLock<Vec<SetlistEntry>> {
+        let entries = programs
+            .iter()
+            .enumerate()
+            .map(|(index, &pc)| SetlistEntry {
+                name: entry_name(index),
+                snapshot: ParamsSnapshot {
+                    pc,
+                    ..Default::default()
+                },
+            })
+            .collect();
+
+        RwLock::new(entries)
+    }
+
+    fn programs(entries: &RwLock<Vec<SetlistEntry>>) -> Vec<u8> {
+        let entries = entries.read().unwrap();
+        entries.iter().map(|entry| entry.snapshot.pc).collect()
+    }
+
+    fn snapshot(pc: u8) -> ParamsSnapshot {
+        ParamsSnapshot {
+            pc,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn steps_stop_at_the_ends() {
+        assert_eq!(Step::Next.apply(0, 3), Some(1));
+        assert_eq!(Step::Next.apply(2, 3), Some(2));
+        assert_eq!(Step::Previous.apply(1, 3), Some(0));
+        assert_eq!(Step::Previous.apply(0, 3), Some(0));
+
+        // a position left past the end after the setlist shrank comes back into it
+        assert_eq!(Step::Next.apply(7, 3), Some(2));
+        assert_eq!(Step::Previous.apply(7, 3), Some(2));
+    }
+
+    #[test]
+    fn select_stays_in_the_setlist() {
+        assert_eq!(Step::Select(2).apply(0, 3), Some(2));
+        assert_eq!(Step::Select(3).apply(0, 3), None);
+        assert_eq!(Step::Next.apply(0, 0), None);
+        assert_eq!(Step::Select(0).apply(0, 0), None);
+    }
+
+    #[test]
+    fn store_replaces_or_appends() {
+        let setlist = entries(&[1, 2]);
+        let current = AtomicUsize::new(1);
+
+        SetlistTask::Store {
+            position: 1,
+            snapshot: snapshot(5),
+        }
+        .run(&setlist, &current);
+        assert_eq!(programs(&setlist), [1, 5]);
+        assert_eq!(current.load(Ordering::Relaxed), 1);
+
+        SetlistTask::Store {
+            position: 4,
+            snapshot: snapshot(6),
+        }
+        .run(&setlist, &current);
+        assert_eq!(programs(&setlist), [1, 5, 6]);
+        assert_eq!(current.load(Ordering::Relaxed), 2);
+        assert_eq!(setlist.read().unwrap()[2].name, "Step 3");
+    }
+
+    #[test]
+    fn insert_goes_after_the_position_and_moves_to_it() {
+        let setlist = entries(&[1, 2]);
+        let current = AtomicUsize::new(0);
+
+        SetlistTask::Insert {
+            position: 0,
+            snapshot: snapshot(7),
+        }
+        .run(&setlist, &current);
+        assert_eq!(programs(&setlist), [1, 7, 2]);
+        assert_eq!(current.load(Ordering::Relaxed), 1);
+
+        SetlistTask::Insert {
+            position: 9,
+            snapshot: snapshot(8),
+        }
+        .run(&setlist, &current);
+        assert_eq!(programs(&setlist), [1, 7, 2, 8]);
+        assert_eq!(current.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn poisoned_setlist_is_left_as_it_is() {
+        let setlist = entries(&[1, 2]);
+        let current = AtomicUsize::new(0);
+
+        let _ = std::panic::catch_unwind(|| {
+            let _entries = setlist.write().unwrap();
+            panic!("an edit panicked");
+        });
+        assert!(setlist.is_poisoned());
+
+        SetlistTask::Insert {
+            position: 0,
+            snapshot: snapshot(7),
+        }
+        .run(&setlist, &current);
+        assert_eq!(current.load(Ordering::Relaxed), 0);
+
+        let entries = setlist
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn trigger_rises_once_per_press() {
+        let mut trigger = Trigger::default();
+
+        assert!(!trigger.rises(false));
+        assert!(trigger.rises(true));
+        assert!(!trigger.rises(true));
+        assert!(!trigger.rises(false));
+        assert!(trigger.rises(true));
+    }
+}
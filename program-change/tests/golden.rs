@@ -0,0 +1,11 @@
+use midi_cli::golden_case;
+
+#[test]
+fn channel_change_with_held_keys() {
+    golden_case(env!("CARGO_MANIFEST_DIR"), "channel-change-held");
+}
+
+#[test]
+fn note_127_transposed_out_of_range() {
+    golden_case(env!("CARGO_MANIFEST_DIR"), "note-127");
+}
@@ -0,0 +1,16 @@
+use midi_cli::stuck_notes::assert_no_stuck_notes;
+use proptest::prelude::*;
+
+/// Every note sent is released on its channel and no note off is sent for a note that was
+/// never opened, whatever the player and the automation of the activation and the channel do.
+#[test]
+fn no_stuck_notes() {
+    assert_no_stuck_notes(
+        "program-change",
+        prop_oneof![
+            any::<bool>()
+                .prop_map(|active| ("Active", if active { "On" } else { "Off" }.to_owned())),
+            (1u8..=4).prop_map(|channel| ("Channel", channel.to_string())),
+        ],
+    );
+}
@@ -1,13 +1,20 @@
 mod params;
+mod presets;
 mod processor;
 mod state;
 
+use midi_common::{EventMonitor, MonitoredContext, NoteMap, StateOverlay, editor};
 use nih_plug::prelude::*;
-use params::SeemlessSwitchParams;
+use params::{FIELDS, ParamsSnapshot, SeemlessSwitchParams};
 use state::InternalState;
 use std::sync::Arc;
 
 pub struct SeemlessSwitch {
+    held: NoteMap,
+    monitor: Arc<EventMonitor>,
+    overlay: Arc<StateOverlay<ParamsSnapshot>>,
+    /// Samples processed since the plugin was activated, the time of the monitor.
+    position: u64,
     state: InternalState,
     params: Arc<SeemlessSwitchParams>,
 }
@@ -15,6 +22,10 @@ pub struct SeemlessSwitch {
 impl Default for SeemlessSwitch {
     fn default() -> Self {
         Self {
+            held: NoteMap::default(),
+            monitor: Default::default(),
+            overlay: Default::default(),
+            position: 0,
             params: Arc::new(SeemlessSwitchParams::default()),
             state: InternalState::default(),
         }
@@ -39,14 +50,47 @@ impl Plugin for SeemlessSwitch {
         self.params.clone()
     }
 
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create_with_state(
+            Self::NAME,
+            self.params.clone(),
+            presets::FACTORY_PRESETS,
+            FIELDS,
+            self.monitor.clone(),
+            self.overlay.clone(),
+            self.params.editor_state.clone(),
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.monitor.set_sample_rate(buffer_config.sample_rate);
+        true
+    }
+
     fn process(
         &mut self,
-        _buffer: &mut Buffer,
+        buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
         ctx: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        let position = self.position;
+        let ctx = &mut MonitoredContext::<Self, _>::new(ctx, &self.monitor, position);
+        let previous_state = self.state.name();
+
         processor::process_state_transitions(&mut self.state, &self.params, ctx);
-        processor::process_events_on_state(&mut self.state, &self.params, ctx);
+        processor::process_events_on_state(&mut self.state, &mut self.held, &self.params, ctx);
+
+        if self.state.name() != previous_state {
+            self.monitor.state(position, self.state.name());
+        }
+
+        self.overlay.publish(self.state.view());
+        self.position += buffer.samples() as u64;
         ProcessStatus::Normal
     }
 }
@@ -0,0 +1,6 @@
+use nih_plug::prelude::*;
+use seemless_switch::SeemlessSwitch;
+
+fn main() {
+    nih_export_standalone::<SeemlessSwitch>();
+}
@@ -1,10 +1,28 @@
+use midi_common::editor::{self, ViziaState};
+use midi_common::{
+    ControllerValue, HoldPolicies, HoldPolicy, MAX_14_BIT, MPE_MANAGER_CHANNEL, ParameterChange,
+    ProgramChangePolicy, ProgramMap, SetupField, VelocityCurve, VelocityTransform, WithProgramMap,
+    controller_value, fine_param, input_channel, input_channel_param, mpe_members,
+    nrpn_param_change, transpose_semitones, velocity_param, velocity_transform,
+};
 use nih_plug::midi::control_change::{EXPRESSION_CONTROLLER_MSB, MODULATION_MSB};
 use nih_plug::prelude::*;
+use std::sync::{Arc, RwLock};
 
 use crate::SeemlessSwitch;
 
+/// The persisted fields a setup may set, see [`SeemlessSwitchParams`].
+pub const FIELDS: &[SetupField] = &[SetupField::of::<ProgramMap>("program-map")];
+
 #[derive(Params)]
 pub struct SeemlessSwitchParams {
+    #[persist = "editor-state"]
+    pub editor_state: Arc<ViziaState>,
+
+    /// The translation table of the incoming program changes.
+    #[persist = "program-map"]
+    pub program_map: RwLock<ProgramMap>,
+
     #[id = "active"]
     pub active: BoolParam,
 
@@ -20,9 +38,66 @@ pub struct SeemlessSwitchParams {
     #[id = "expr"]
     pub expr: IntParam,
 
+    #[id = "expr14"]
+    pub expr_fine: IntParam,
+
+    #[id = "in"]
+    pub input_channel: IntParam,
+
+    #[id = "inpass"]
+    pub pass_other: BoolParam,
+
     #[id = "mw"]
     pub mw: IntParam,
 
+    #[id = "mw14"]
+    pub mw_fine: IntParam,
+
+    #[id = "hat"]
+    pub hold_aftertouch: EnumParam<HoldPolicy>,
+
+    #[id = "hcc"]
+    pub hold_controllers: EnumParam<HoldPolicy>,
+
+    #[id = "hpb"]
+    pub hold_pitch_bend: EnumParam<HoldPolicy>,
+
+    #[id = "hpoly"]
+    pub hold_poly: EnumParam<HoldPolicy>,
+
+    #[id = "mpe"]
+    pub mpe: BoolParam,
+
+    #[id = "mpem"]
+    pub mpe_members: IntParam,
+
+    #[id = "nrpn"]
+    pub nrpn: IntParam,
+
+    #[id = "nrpnv"]
+    pub nrpn_value: IntParam,
+
+    #[id = "pcin"]
+    pub incoming_program: EnumParam<ProgramChangePolicy>,
+
+    #[id = "oct"]
+    pub octave: IntParam,
+
+    #[id = "transp"]
+    pub transpose: IntParam,
+
+    #[id = "velc"]
+    pub velocity_curve: EnumParam<VelocityCurve>,
+
+    #[id = "velf"]
+    pub velocity_fixed: IntParam,
+
+    #[id = "velmax"]
+    pub velocity_max: IntParam,
+
+    #[id = "velmin"]
+    pub velocity_min: IntParam,
+
     #[id = "slider1"]
     pub slider1: IntParam,
 
@@ -75,12 +150,52 @@ pub struct SeemlessSwitchParams {
 impl Default for SeemlessSwitchParams {
     fn default() -> Self {
         Self {
+            editor_state: editor::default_state(),
+            program_map: Default::default(),
             active: BoolParam::new("Active", true),
             allow_mod_wheel: BoolParam::new("Allow Mod Wheel", true),
             allow_pitch_bend: BoolParam::new("Allow Pitch Bend", true),
             allow_sustain: BoolParam::new("Allow Sustain", true),
             expr: IntParam::new("Expression", -1, IntRange::Linear { min: -1, max: 127 }),
+            expr_fine: fine_param("Expression (14-bit)"),
+            input_channel: input_channel_param(),
+            pass_other: BoolParam::new("Pass Other Channels", false),
             mw: IntParam::new("Mod Wheel", -1, IntRange::Linear { min: -1, max: 127 }),
+            mw_fine: fine_param("Mod Wheel (14-bit)"),
+            hold_aftertouch: EnumParam::new("Hold Aftertouch", HoldPolicy::Drop),
+            hold_controllers: EnumParam::new("Hold Controllers", HoldPolicy::Drop),
+            hold_pitch_bend: EnumParam::new("Hold Pitch Bend", HoldPolicy::Drop),
+            hold_poly: EnumParam::new("Hold Poly Expression", HoldPolicy::Drop),
+            mpe: BoolParam::new("MPE", false),
+            mpe_members: IntParam::new(
+                "MPE Member Channels",
+                15,
+                IntRange::Linear { min: 1, max: 15 },
+            ),
+            nrpn: IntParam::new(
+                "NRPN Number",
+                -1,
+                IntRange::Linear {
+                    min: -1,
+                    max: MAX_14_BIT as i32,
+                },
+            ),
+            nrpn_value: IntParam::new(
+                "NRPN Value",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_14_BIT as i32,
+                },
+            ),
+            incoming_program: EnumParam::new("Incoming Program Change", ProgramChangePolicy::Drop),
+            octave: IntParam::new("Octave", 0, IntRange::Linear { min: -4, max: 4 }),
+            transpose: IntParam::new("Transpose", 0, IntRange::Linear { min: -24, max: 24 })
+                .with_unit(" st"),
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::Linear),
+            velocity_fixed: velocity_param("Fixed Velocity", 100),
+            velocity_max: velocity_param("Velocity Max", 127),
+            velocity_min: velocity_param("Velocity Min", 1),
             slider1: IntParam::new("Slider 1 (82)", -1, IntRange::Linear { min: -1, max: 127 }),
             slider2: IntParam::new("Slider 2 (83)", -1, IntRange::Linear { min: -1, max: 127 }),
             slider3: IntParam::new("Slider 3 (84)", -1, IntRange::Linear { min: -1, max: 127 }),
@@ -102,11 +217,40 @@ impl Default for SeemlessSwitchParams {
     }
 }
 
+impl WithProgramMap for SeemlessSwitchParams {
+    fn program_map(&self) -> &RwLock<ProgramMap> {
+        &self.program_map
+    }
+}
+
 impl SeemlessSwitchParams {
+    pub fn hold_policies(&self) -> HoldPolicies {
+        HoldPolicies {
+            pitch_bend: self.hold_pitch_bend.value(),
+            aftertouch: self.hold_aftertouch.value(),
+            controllers: self.hold_controllers.value(),
+            poly: self.hold_poly.value(),
+        }
+    }
+
+    /// The input channel, `None` in omni mode.
+    pub fn input_channel(&self) -> Option<u8> {
+        input_channel(&self.input_channel)
+    }
+
     pub fn snapshot(&self) -> ParamsSnapshot {
         ParamsSnapshot {
-            expr: set_value_range_opt(&self.expr),
-            mw: set_value_range_opt(&self.mw),
+            expr: controller_value(&self.expr, &self.expr_fine),
+            mw: controller_value(&self.mw, &self.mw_fine),
+            mpe: mpe_members(&self.mpe, &self.mpe_members),
+            nrpn: nrpn_param_change(&self.nrpn, &self.nrpn_value),
+            transpose: transpose_semitones(&self.transpose, &self.octave),
+            velocity: velocity_transform(
+                &self.velocity_curve,
+                &self.velocity_fixed,
+                &self.velocity_min,
+                &self.velocity_max,
+            ),
 
             knob1: set_value_range_opt(&self.knob1),
             knob2: set_value_range_opt(&self.knob2),
@@ -139,9 +283,16 @@ fn set_value_range_opt(v: &IntParam) -> Option<u8> {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct ParamsSnapshot {
-    pub expr: Option<u8>,
-    pub mw: Option<u8>,
+    pub expr: Option<ControllerValue>,
+    pub mw: Option<ControllerValue>,
+    pub mpe: Option<u8>,
+    pub nrpn: Option<ParameterChange>,
+    /// Semitones added to the notes played on the patch.
+    pub transpose: i8,
+    /// Applied to the note ons played on the patch, note offs keep their release velocity.
+    pub velocity: VelocityTransform,
 
     pub knob1: Option<u8>,
     pub knob2: Option<u8>,
@@ -163,13 +314,22 @@ pub struct ParamsSnapshot {
 }
 
 impl ParamsSnapshot {
+    /// The output channel and note of a new note: its member channel in MPE mode, the first
+    /// channel otherwise, transposed. Notes pushed out of the MIDI range are dropped.
+    pub fn new_note(&self, channel: u8, note: u8) -> Option<(u8, u8)> {
+        let channel = if self.mpe.is_some() { channel } else { 0 };
+        let note = note as i16 + self.transpose as i16;
+
+        (0..128).contains(&note).then_some((channel, note as u8))
+    }
+
     pub fn send(
         &self,
         old: Option<&ParamsSnapshot>,
         context: &mut impl ProcessContext<crate::SeemlessSwitch>,
     ) {
-        send_cc(old, self, |p| p.expr, EXPRESSION_CONTROLLER_MSB, context);
-        send_cc(old, self, |p| p.mw, MODULATION_MSB, context);
+        send_controller(old, self, |p| p.expr, EXPRESSION_CONTROLLER_MSB, context);
+        send_controller(old, self, |p| p.mw, MODULATION_MSB, context);
         send_cc(old, self, |p| p.knob1, 16, context);
         send_cc(old, self, |p| p.knob2, 17, context);
         send_cc(old, self, |p| p.knob3, 18, context);
@@ -186,6 +346,16 @@ impl ParamsSnapshot {
         send_cc(old, self, |p| p.slider6, 87, context);
         send_cc(old, self, |p| p.slider7, 88, context);
         send_cc(old, self, |p| p.slider8, 89, context);
+        send_parameter(old, self, |p| p.nrpn, context);
+        send_parameter(
+            old,
+            self,
+            |p| {
+                p.mpe
+                    .map(|members| ParameterChange::mpe_configuration(members).terminated())
+            },
+            context,
+        );
     }
 }
 
@@ -207,3 +377,35 @@ fn send_cc(
         });
     }
 }
+
+fn send_controller(
+    old: Option<&ParamsSnapshot>,
+    new: &ParamsSnapshot,
+    map: impl Fn(&ParamsSnapshot) -> Option<ControllerValue> + Copy,
+    controller: u8,
+    context: &mut impl ProcessContext<SeemlessSwitch>,
+) {
+    if let Some(new) = map(new)
+        && old.and_then(map).is_none_or(|old| old != new)
+    {
+        for cc in new.messages(0, controller) {
+            context.send_event(cc.to_event(0));
+        }
+    }
+}
+
+fn send_parameter(
+    old: Option<&ParamsSnapshot>,
+    new: &ParamsSnapshot,
+    map: impl Fn(&ParamsSnapshot) -> Option<ParameterChange> + Copy,
+    context: &mut impl ProcessContext<SeemlessSwitch>,
+) {
+    if let Some(new) = map(new)
+        && old.and_then(map).is_none_or(|old| old != new)
+    {
+        // everything goes to the first channel, which is also the MPE manager channel
+        for cc in new.messages(MPE_MANAGER_CHANNEL) {
+            context.send_event(cc.to_event(0));
+        }
+    }
+}
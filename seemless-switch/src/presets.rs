@@ -0,0 +1,24 @@
+/// The factory presets, stepped through in the editor.
+pub const FACTORY_PRESETS: &[&str] = &[
+    include_str!("../presets/piano.json"),
+    include_str!("../presets/organ.json"),
+    include_str!("../presets/synth-lead.json"),
+    include_str!("../presets/mpe.json"),
+    include_str!("../presets/eight-knobs-eight-sliders.json"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::SeemlessSwitchParams;
+    use midi_common::check_presets;
+
+    #[test]
+    fn factory_presets_are_valid() {
+        let presets = check_presets(FACTORY_PRESETS, &SeemlessSwitchParams::default()).unwrap();
+        let files = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/presets")).unwrap();
+
+        // every file of the directory is listed
+        assert_eq!(presets.len(), files.count());
+    }
+}
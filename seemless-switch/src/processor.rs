@@ -1,6 +1,7 @@
 use crate::SeemlessSwitch;
-use crate::params::SeemlessSwitchParams;
-use crate::state::{DamperState, InternalState, NotesState};
+use crate::params::{ParamsSnapshot, SeemlessSwitchParams};
+use crate::state::InternalState;
+use midi_common::{DamperState, NoteMap, NotesState, next_input_event, with_channel};
 use nih_plug::midi::control_change::{DAMPER_PEDAL, MODULATION_MSB};
 use nih_plug::prelude::*;
 use std::mem::take;
@@ -76,21 +77,48 @@ pub fn process_state_transitions(
 
 pub fn process_events_on_state(
     state: &mut InternalState,
+    held: &mut NoteMap,
     params: &SeemlessSwitchParams,
     ctx: &mut impl ProcessContext<SeemlessSwitch>,
 ) {
+    let input = params.input_channel();
+    let pass_other = params.pass_other.value();
+
     match state {
-        InternalState::Off => {}
+        InternalState::Off => {
+            // only the events of the other channels go through while inactive
+            while next_input_event(input, pass_other, ctx).is_some() {}
+        }
+
+        InternalState::On {
+            damper,
+            notes,
+            snapshot,
+        } => {
+            while let Some(event) = next_input_event(input, pass_other, ctx) {
+                let Some(event) = held.route(event, notes, |channel, note| {
+                    snapshot.new_note(channel, note)
+                }) else {
+                    continue;
+                };
 
-        InternalState::On { damper, notes, .. } => {
-            while let Some(event) = ctx.next_event() {
-                process_event_on(ctx, event, damper, notes, params);
+                process_event_on(ctx, event, damper, notes, params, snapshot);
             }
         }
 
-        InternalState::SeamlessSwitch { damper, notes, .. } => {
-            while let Some(event) = ctx.next_event() {
-                process_event_seamless(ctx, event, damper, notes);
+        InternalState::SeamlessSwitch {
+            damper,
+            notes,
+            snapshot,
+        } => {
+            while let Some(event) = next_input_event(input, pass_other, ctx) {
+                let Some(event) = held.route(event, notes, |channel, note| {
+                    snapshot.new_note(channel, note)
+                }) else {
+                    continue;
+                };
+
+                process_event_seamless(ctx, event, damper, notes, params, snapshot);
             }
 
             if damper.is_off() && notes.is_all_off() {
@@ -106,7 +134,11 @@ fn process_event_on(
     damper: &mut DamperState,
     notes: &mut NotesState,
     params: &SeemlessSwitchParams,
+    snapshot: &ParamsSnapshot,
 ) {
+    let mpe = snapshot.mpe.is_some();
+    let channel = output_channel(&event, mpe);
+
     match event {
         NoteEvent::Choke {
             timing,
@@ -114,11 +146,11 @@ fn process_event_on(
             note,
             ..
         } => {
-            notes.set_off(note);
+            notes.set_off(channel, note);
             ctx.send_event(NoteEvent::Choke {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
             });
         }
@@ -155,7 +187,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::MidiCC {
                 timing,
                 cc,
-                channel: 0,
+                channel,
                 value,
             });
         }
@@ -165,7 +197,7 @@ fn process_event_on(
         } => {
             ctx.send_event(NoteEvent::MidiChannelPressure {
                 timing,
-                channel: 0,
+                channel,
                 pressure,
             });
         }
@@ -174,14 +206,22 @@ fn process_event_on(
             if params.allow_pitch_bend.value() {
                 ctx.send_event(NoteEvent::MidiPitchBend {
                     timing,
-                    channel: 0,
+                    channel,
                     value,
                 });
             }
         }
 
-        NoteEvent::MidiProgramChange { .. } => {
-            // Ignore program change events
+        NoteEvent::MidiProgramChange {
+            timing, program, ..
+        } => {
+            params.incoming_program.value().send(
+                timing,
+                channel,
+                program,
+                &params.program_map,
+                ctx,
+            );
         }
 
         NoteEvent::NoteOff {
@@ -191,11 +231,11 @@ fn process_event_on(
             velocity,
             ..
         } => {
-            notes.set_off(note);
+            notes.set_off(channel, note);
             ctx.send_event(NoteEvent::NoteOff {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 velocity,
             });
@@ -208,13 +248,13 @@ fn process_event_on(
             velocity,
             ..
         } => {
-            notes.set_on(note);
+            notes.set_on(channel, note);
             ctx.send_event(NoteEvent::NoteOn {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
-                velocity,
+                velocity: snapshot.velocity.apply(velocity),
             });
         }
 
@@ -228,7 +268,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::PolyBrightness {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 brightness,
             });
@@ -244,7 +284,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::PolyExpression {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 expression,
             });
@@ -260,7 +300,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::PolyPan {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 pan,
             });
@@ -276,7 +316,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::PolyPressure {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 pressure,
             });
@@ -292,7 +332,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::PolyTuning {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 tuning,
             });
@@ -308,7 +348,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::PolyVibrato {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 vibrato,
             });
@@ -324,7 +364,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::PolyVolume {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 gain,
             });
@@ -339,7 +379,7 @@ fn process_event_on(
             ctx.send_event(NoteEvent::VoiceTerminated {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
             });
         }
@@ -353,20 +393,29 @@ fn process_event_seamless(
     event: NoteEvent<()>,
     damper: &mut DamperState,
     notes: &mut NotesState,
+    params: &SeemlessSwitchParams,
+    snapshot: &ParamsSnapshot,
 ) {
+    let mpe = snapshot.mpe.is_some();
+    let channel = output_channel(&event, mpe);
+
     match event {
+        event if forwards_on_hold(&event, channel, notes, params, mpe) => {
+            ctx.send_event(with_channel(event, channel))
+        }
+
         NoteEvent::Choke {
             timing,
             voice_id,
             note,
             ..
         } => {
-            notes.set_off(note);
+            notes.set_off(channel, note);
             damper.set_off();
             ctx.send_event(NoteEvent::Choke {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
             });
         }
@@ -378,7 +427,7 @@ fn process_event_seamless(
                 damper.set_off();
                 ctx.send_event(NoteEvent::MidiCC {
                     timing,
-                    channel: 0,
+                    channel,
                     cc,
                     value: 0.0,
                 });
@@ -392,11 +441,11 @@ fn process_event_seamless(
             velocity,
             ..
         } => {
-            notes.set_off(note);
+            notes.set_off(channel, note);
             ctx.send_event(NoteEvent::NoteOff {
                 timing,
                 voice_id,
-                channel: 0,
+                channel,
                 note,
                 velocity,
             });
@@ -407,3 +456,28 @@ fn process_event_seamless(
         }
     }
 }
+
+/// Whether the event is forwarded while notes are held, events disabled by the allow parameters
+/// never are.
+fn forwards_on_hold(
+    event: &NoteEvent<()>,
+    channel: u8,
+    notes: &NotesState,
+    params: &SeemlessSwitchParams,
+    mpe: bool,
+) -> bool {
+    let allowed = match event {
+        NoteEvent::MidiPitchBend { .. } => params.allow_pitch_bend.value(),
+        NoteEvent::MidiCC {
+            cc: MODULATION_MSB, ..
+        } => params.allow_mod_wheel.value(),
+        _ => true,
+    };
+
+    allowed && params.hold_policies().forwards(event, channel, notes, mpe)
+}
+
+/// Events keep their member channel in MPE mode, everything goes to the first channel otherwise.
+fn output_channel(event: &NoteEvent<()>, mpe: bool) -> u8 {
+    if mpe { event.channel().unwrap_or(0) } else { 0 }
+}
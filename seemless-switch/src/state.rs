@@ -1,43 +1,5 @@
 use crate::params::ParamsSnapshot;
-
-#[derive(Debug, Default, Clone, Copy)]
-pub struct NotesState(pub u128);
-
-impl NotesState {
-    pub fn is_all_off(&self) -> bool {
-        self.0 == 0
-    }
-
-    pub fn set_off(&mut self, note: u8) {
-        self.0 &= !note_mask(note);
-    }
-
-    pub fn set_on(&mut self, note: u8) {
-        self.0 |= note_mask(note);
-    }
-}
-
-#[inline]
-fn note_mask(note: u8) -> u128 {
-    1 << note
-}
-
-#[derive(Debug, Default, Clone, Copy)]
-pub struct DamperState(bool);
-
-impl DamperState {
-    pub fn is_off(&self) -> bool {
-        !self.0
-    }
-
-    pub fn set_off(&mut self) {
-        self.0 = false;
-    }
-
-    pub fn set_on(&mut self) {
-        self.0 = true;
-    }
-}
+use midi_common::{DamperState, NotesState, StateView};
 
 pub enum InternalState {
     On {
@@ -58,3 +20,42 @@ impl Default for InternalState {
         Self::Off
     }
 }
+
+impl InternalState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::On { .. } => "On",
+            Self::SeamlessSwitch { .. } => "Seamless switch",
+            Self::Off => "Off",
+        }
+    }
+
+    /// The state for the editor, all the notes of a seamless switch are held through it.
+    pub fn view(&self) -> StateView<ParamsSnapshot> {
+        match *self {
+            Self::On {
+                damper,
+                notes,
+                snapshot,
+            } => StateView {
+                state: self.name(),
+                notes,
+                switched: NotesState::default(),
+                damper,
+                snapshot: Some(snapshot),
+            },
+            Self::SeamlessSwitch {
+                damper,
+                notes,
+                snapshot,
+            } => StateView {
+                state: self.name(),
+                notes,
+                switched: notes,
+                damper,
+                snapshot: Some(snapshot),
+            },
+            Self::Off => StateView::off(),
+        }
+    }
+}
@@ -0,0 +1,6 @@
+use midi_cli::golden_case;
+
+#[test]
+fn sustain_held_through_deactivate() {
+    golden_case(env!("CARGO_MANIFEST_DIR"), "sustain-through-deactivate");
+}
@@ -0,0 +1,12 @@
+use midi_cli::stuck_notes::assert_no_stuck_notes;
+use proptest::prelude::*;
+
+/// Every note sent is released on its channel and no note off is sent for a note that was
+/// never opened, whatever the player and the automation of the activation do.
+#[test]
+fn no_stuck_notes() {
+    assert_no_stuck_notes(
+        "seemless-switch",
+        any::<bool>().prop_map(|active| ("Active", if active { "On" } else { "Off" }.to_owned())),
+    );
+}
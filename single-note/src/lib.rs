@@ -1,15 +1,28 @@
+mod presets;
+
+use midi_common::editor::{self, ViziaState};
+use midi_common::{EventMonitor, MonitoredContext};
 use nih_plug::prelude::*;
 use std::sync::Arc;
 
-struct SingleNote {
+pub struct SingleNote {
+    monitor: Arc<EventMonitor>,
     params: Arc<SingleNoteParams>,
+    /// Samples processed since the plugin was activated, the time of the monitor.
+    position: u64,
+    /// The channel and note of the single note while it plays, it is released there even if
+    /// the target note changed meanwhile.
+    sounding: Option<(u8, u8)>,
     states: NotesState,
 }
 
 impl Default for SingleNote {
     fn default() -> Self {
         Self {
+            monitor: Default::default(),
             params: Arc::new(SingleNoteParams::default()),
+            position: 0,
+            sounding: None,
             states: Default::default(),
         }
     }
@@ -36,12 +49,35 @@ impl Plugin for SingleNote {
         self.params.clone()
     }
 
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            Self::NAME,
+            self.params.clone(),
+            presets::FACTORY_PRESETS,
+            self.monitor.clone(),
+            self.params.editor_state.clone(),
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.monitor.set_sample_rate(buffer_config.sample_rate);
+        true
+    }
+
     fn process(
         &mut self,
-        _buffer: &mut Buffer,
+        buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        let context = &mut MonitoredContext::<Self, _>::new(context, &self.monitor, self.position);
+        self.position += buffer.samples() as u64;
+
         while let Some(event) = context.next_event() {
             match event {
                 NoteEvent::NoteOn {
@@ -56,11 +92,14 @@ impl Plugin for SingleNote {
                     self.states.set_on(note);
 
                     if is_off {
+                        let note = self.params.note();
+
+                        self.sounding = Some((channel, note));
                         context.send_event(NoteEvent::NoteOn {
                             timing,
                             voice_id,
                             channel,
-                            note: self.params.note(),
+                            note,
                             velocity,
                         });
                     }
@@ -68,20 +107,22 @@ impl Plugin for SingleNote {
                 NoteEvent::NoteOff {
                     timing,
                     voice_id,
-                    channel,
                     note,
                     velocity,
+                    ..
                 } => {
                     self.states.set_off(note);
 
                     let is_off = self.states.is_all_off();
 
-                    if is_off {
+                    // released once, a note off of a key that was not held sends nothing
+                    if let Some((channel, note)) = self.sounding.filter(|_| is_off) {
+                        self.sounding = None;
                         context.send_event(NoteEvent::NoteOff {
                             timing,
                             voice_id,
                             channel,
-                            note: self.params.note(),
+                            note,
                             velocity,
                         });
                     }
@@ -119,6 +160,9 @@ fn note_mask(note: u8) -> u128 {
 
 #[derive(Params)]
 struct SingleNoteParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+
     #[id = "note"]
     note: IntParam,
 }
@@ -126,7 +170,14 @@ struct SingleNoteParams {
 impl Default for SingleNoteParams {
     fn default() -> Self {
         Self {
-            note: IntParam::new("Target note", 0, IntRange::Linear { min: 0, max: 127 }),
+            editor_state: editor::default_state(),
+            note: IntParam::new("Target note", 0, IntRange::Linear { min: 0, max: 127 })
+                .with_value_to_string(formatters::v2s_i32_note_formatter())
+                .with_string_to_value({
+                    // the note number is accepted as well as its name
+                    let note_name = formatters::s2v_i32_note_formatter();
+                    Arc::new(move |string| string.trim().parse().ok().or_else(|| note_name(string)))
+                }),
         }
     }
 }
@@ -0,0 +1,6 @@
+use nih_plug::prelude::*;
+use single_note::SingleNote;
+
+fn main() {
+    nih_export_standalone::<SingleNote>();
+}
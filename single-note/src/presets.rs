@@ -0,0 +1,23 @@
+/// The factory presets, stepped through in the editor.
+pub const FACTORY_PRESETS: &[&str] = &[
+    include_str!("../presets/kick.json"),
+    include_str!("../presets/snare.json"),
+    include_str!("../presets/closed-hi-hat.json"),
+    include_str!("../presets/middle-c.json"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SingleNoteParams;
+    use midi_common::check_presets;
+
+    #[test]
+    fn factory_presets_are_valid() {
+        let presets = check_presets(FACTORY_PRESETS, &SingleNoteParams::default()).unwrap();
+        let files = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/presets")).unwrap();
+
+        // every file of the directory is listed
+        assert_eq!(presets.len(), files.count());
+    }
+}
@@ -0,0 +1,16 @@
+use midi_cli::golden_case;
+
+#[test]
+fn overlapping_notes() {
+    golden_case(env!("CARGO_MANIFEST_DIR"), "overlapping-notes");
+}
+
+#[test]
+fn retriggered_note() {
+    golden_case(env!("CARGO_MANIFEST_DIR"), "retrigger");
+}
+
+#[test]
+fn note_127() {
+    golden_case(env!("CARGO_MANIFEST_DIR"), "note-127");
+}
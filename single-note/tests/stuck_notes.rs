@@ -0,0 +1,12 @@
+use midi_cli::stuck_notes::{assert_no_stuck_notes, note};
+use proptest::prelude::*;
+
+/// Every note sent is released on its channel and no note off is sent for a note that was
+/// never opened, whatever the player and the automation of the target note do.
+#[test]
+fn no_stuck_notes() {
+    assert_no_stuck_notes(
+        "single-note",
+        note().prop_map(|note| ("Target note", note.to_string())),
+    );
+}